@@ -0,0 +1,149 @@
+//! Hash algorithms supported by the command line interface.
+
+use sha_256_scratch::{Digest, Hmac, Sha224, Sha256, Sha256d};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    Sha256,
+    Sha224,
+    Sha256d, // SHA256(SHA256(m))
+}
+
+impl Algorithm {
+    pub const ALL: [Algorithm; 3] = [Algorithm::Sha256, Algorithm::Sha224, Algorithm::Sha256d];
+
+    /// Parses the name used by `-a/--algorithm`
+    pub fn from_name(name: &str) -> Option<Algorithm> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name.to_ascii_lowercase())
+    }
+
+    /// Parses the tag starting BSD-style lines, such as `SHA256 (file) = ...`
+    pub fn from_bsd_tag(tag: &str) -> Option<Algorithm> {
+        Self::ALL
+            .into_iter()
+            .find(|algorithm| algorithm.bsd_tag() == tag)
+    }
+
+    /// Infers the algorithm from the binary name, e.g. when symlinked as `sha224sum_from_scratch`
+    pub fn from_binary_name(binary: &str) -> Option<Algorithm> {
+        let name = Path::new(binary).file_stem()?.to_str()?;
+        let prefix = name.split("sum").next()?;
+
+        Self::from_name(prefix)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "sha256",
+            Algorithm::Sha224 => "sha224",
+            Algorithm::Sha256d => "sha256d",
+        }
+    }
+
+    pub fn bsd_tag(&self) -> &'static str {
+        match self {
+            Algorithm::Sha256 => "SHA256",
+            Algorithm::Sha224 => "SHA224",
+            Algorithm::Sha256d => "SHA256D",
+        }
+    }
+
+    /// Digest length in bytes
+    pub fn digest_len(&self) -> usize {
+        match self {
+            Algorithm::Sha256 | Algorithm::Sha256d => 32,
+            Algorithm::Sha224 => 28,
+        }
+    }
+
+    pub fn hasher(&self) -> Hasher {
+        match self {
+            Algorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            Algorithm::Sha224 => Hasher::Sha224(Sha224::new()),
+            Algorithm::Sha256d => Hasher::Sha256d(Sha256d::new()),
+        }
+    }
+}
+
+/// Incremental hasher of any supported algorithm, or HMAC-SHA256
+pub enum Hasher {
+    Sha256(Sha256),
+    Sha224(Sha224),
+    Sha256d(Sha256d),
+    Hmac(Hmac),
+}
+
+impl Hasher {
+    /// Plain hasher of `algorithm`, or HMAC-SHA256 when a key is given
+    pub fn new(algorithm: Algorithm, hmac_key: Option<&[u8]>) -> Hasher {
+        match hmac_key {
+            Some(key) => Hasher::Hmac(Hmac::new(key)),
+            None => algorithm.hasher(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Sha256(hasher) => hasher.update(data),
+            Hasher::Sha224(hasher) => hasher.update(data),
+            Hasher::Sha256d(hasher) => hasher.update(data),
+            Hasher::Hmac(hmac) => hmac.update(data),
+        }
+    }
+
+    pub fn finalize(self) -> Digest {
+        match self {
+            Hasher::Sha256(hasher) => hasher.finalize(),
+            Hasher::Sha224(hasher) => hasher.finalize(),
+            Hasher::Sha256d(hasher) => hasher.finalize(),
+            Hasher::Hmac(hmac) => hmac.finalize(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_binary_name_test() {
+        assert_eq!(
+            Algorithm::from_binary_name("/usr/bin/sha224sum_from_scratch"),
+            Some(Algorithm::Sha224)
+        );
+        assert_eq!(
+            Algorithm::from_binary_name("sha256sum_from_scratch.exe"),
+            Some(Algorithm::Sha256)
+        );
+        assert_eq!(Algorithm::from_binary_name("hasher"), None);
+    }
+
+    #[test]
+    fn from_bsd_tag_test() {
+        assert_eq!(Algorithm::from_bsd_tag("SHA256D"), Some(Algorithm::Sha256d));
+        assert_eq!(Algorithm::from_bsd_tag("sha256d"), None);
+    }
+
+    #[test]
+    fn sha256d_hasher() {
+        let mut hasher = Algorithm::Sha256d.hasher();
+        hasher.update(b"hi");
+
+        let digest = hasher.finalize();
+        assert_eq!(digest.as_bytes().len(), Algorithm::Sha256d.digest_len());
+        assert_eq!(
+            digest.to_hex(),
+            "bc4f48d7a8651dc97ae415f0b47a52ef1a2702098202392b88bc925f6e89ee17"
+        );
+    }
+
+    #[test]
+    fn from_name_test() {
+        assert_eq!(Algorithm::from_name("SHA224"), Some(Algorithm::Sha224));
+        assert_eq!(Algorithm::from_name("sha256d"), Some(Algorithm::Sha256d));
+        assert_eq!(Algorithm::from_name("md5"), None);
+    }
+}
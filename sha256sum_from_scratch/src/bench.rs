@@ -0,0 +1,378 @@
+//! `bench` subcommand: measures the throughput and the memory of each way of reading a file to hash it.
+//!
+//! `bench [--size <SIZE>] [--buffer-size <SIZE>]... [FILE]` hashes FILE, or a temporary file of
+//! SIZE pseudo-random bytes (128 MiB by default), with each strategy:
+//! - `read`: the whole file loaded by `fs::read`, then hashed by `sha_256`, which grows it to pad it
+//! - `buffered`: streamed through a buffer of each given size (4 KiB, 64 KiB and 1 MiB by default), as hashing does
+//! - `mmap`: hashed from a memory mapping, with the `mmap` feature
+//!
+//! The file is hashed once before the runs, so every run finds it in the page cache: the figures
+//! compare the strategies, not the disk. The memory column is how much the peak resident set size
+//! grew during the run, read from `/proc/self/status` after resetting the peak through
+//! `/proc/self/clear_refs`, so it is only available on Linux. Mapped pages count as resident once
+//! read, even though the kernel can drop them at any time since they are backed by the file.
+
+use crate::algorithm::Algorithm;
+use crate::error::Error;
+use crate::input::{self, ByteRange, BUFFER_SIZE};
+use crate::{mmap, option_value, parse_buffer_size, size, split_option, temp};
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+const DEFAULT_SIZE: u64 = 128 << 20;
+const DEFAULT_BUFFER_SIZES: [usize; 3] = [4 << 10, 64 << 10, 1 << 20];
+
+#[derive(Debug, PartialEq)]
+pub struct BenchCommand {
+    pub file: Option<String>, // a temporary file is generated when not set
+    pub size: u64,            // of the generated file
+    pub buffer_sizes: Vec<usize>,
+}
+
+impl BenchCommand {
+    /// Parses the arguments following `bench`
+    pub fn parse_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<BenchCommand, &'static str> {
+        let mut files = Vec::new();
+        let mut size = None;
+        let mut buffer_sizes = Vec::new();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                files.push(arg);
+                continue;
+            }
+
+            let (name, inline_value) = split_option(&arg);
+
+            match name {
+                "-s" | "--size" => {
+                    let value = option_value(inline_value, &mut args)
+                        .ok_or("No file size has been provided")?;
+
+                    size = Some(size::parse_size(&value).ok_or(
+                        "The file size must be a number of bytes, optionally followed by K, M, G, KiB, MiB or GiB",
+                    )?);
+                }
+                "--buffer-size" => buffer_sizes.push(parse_buffer_size(inline_value, &mut args)?),
+                _ => return Err("Unknown bench option"),
+            }
+        }
+
+        if files.len() > 1 {
+            return Err("bench expects at most one file");
+        }
+
+        let file = files.pop();
+
+        if file.is_some() && size.is_some() {
+            return Err(
+                "--size sets the size of the generated file, it can't be given with a file",
+            );
+        }
+
+        if buffer_sizes.is_empty() {
+            buffer_sizes = DEFAULT_BUFFER_SIZES.to_vec();
+        }
+
+        Ok(BenchCommand {
+            file,
+            size: size.unwrap_or(DEFAULT_SIZE),
+            buffer_sizes,
+        })
+    }
+
+    pub fn run(&self) -> Result<(), Error> {
+        let temp_file;
+
+        let path = match &self.file {
+            Some(file) => Path::new(file),
+            None => {
+                temp_file = TempFile::generate(self.size)?;
+                &temp_file.path
+            }
+        };
+
+        self.bench(path, &mut io::stdout().lock())
+    }
+
+    /// Hashes the file with every strategy, checking they all compute the same digest
+    fn bench(&self, path: &Path, out: &mut impl Write) -> Result<(), Error> {
+        let io_error = |err| Error::io(path.display(), err);
+
+        let file_size = fs::metadata(path).map_err(io_error)?.len();
+
+        // loads the file in the page cache
+        let reference = Strategy::Buffered(BUFFER_SIZE)
+            .hash(path)
+            .map_err(io_error)?;
+
+        writeln!(
+            out,
+            "{} ({file_size} bytes, sha256 {reference})",
+            path.display()
+        )?;
+        writeln!(
+            out,
+            "{:<18} {:>10} {:>10} {:>18}",
+            "strategy", "time (s)", "MiB/s", "peak memory (MiB)"
+        )?;
+
+        for strategy in self.strategies() {
+            let run = Run::measure(strategy, path).map_err(io_error)?;
+
+            if run.digest != reference {
+                return Err(Error::Failed(format!(
+                    "the {} strategy computed another digest",
+                    strategy.name()
+                )));
+            }
+
+            let seconds = run.elapsed.as_secs_f64();
+            let throughput = match seconds > 0.0 {
+                true => format!("{:.1}", file_size as f64 / seconds / MIB),
+                false => String::from("-"),
+            };
+            let memory = match run.peak_growth {
+                Some(bytes) => format!("{:.1}", bytes as f64 / MIB),
+                None => String::from("n/a"),
+            };
+
+            writeln!(
+                out,
+                "{:<18} {:>10.3} {:>10} {:>18}",
+                strategy.name(),
+                seconds,
+                throughput,
+                memory
+            )?;
+        }
+
+        Ok(())
+    }
+
+    fn strategies(&self) -> Vec<Strategy> {
+        let buffered = self
+            .buffer_sizes
+            .iter()
+            .map(|&size| Strategy::Buffered(size));
+        let mapped = cfg!(feature = "mmap").then_some(Strategy::Mmap);
+
+        std::iter::once(Strategy::Read)
+            .chain(buffered)
+            .chain(mapped)
+            .collect()
+    }
+}
+
+const MIB: f64 = (1 << 20) as f64;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Strategy {
+    Read,
+    Buffered(usize), // buffer size
+    Mmap,
+}
+
+impl Strategy {
+    fn name(&self) -> String {
+        match self {
+            Strategy::Read => String::from("read"),
+            Strategy::Buffered(size) => format!("buffered {}", format_size(*size)),
+            Strategy::Mmap => String::from("mmap"),
+        }
+    }
+
+    /// Hex digest of the file, read with this strategy
+    fn hash(&self, path: &Path) -> io::Result<String> {
+        let hasher = Algorithm::Sha256.hasher();
+
+        match self {
+            Strategy::Read => Ok(sha_256_scratch::sha_256(fs::read(path)?)),
+            Strategy::Buffered(size) => {
+                let (digest, _) = input::hash_buffered(File::open(path)?, hasher, *size)?;
+                Ok(digest.to_hex())
+            }
+            Strategy::Mmap => {
+                let (digest, _) = mmap::hash_mapped(path, ByteRange::default(), hasher)?;
+                Ok(digest.to_hex())
+            }
+        }
+    }
+}
+
+/// Outcome of hashing the file with one strategy
+struct Run {
+    digest: String,
+    elapsed: Duration,
+    peak_growth: Option<u64>, // in bytes, unknown outside Linux
+}
+
+impl Run {
+    fn measure(strategy: Strategy, path: &Path) -> io::Result<Run> {
+        let baseline = reset_peak_rss();
+
+        let start = Instant::now();
+        let digest = strategy.hash(path)?;
+        let elapsed = start.elapsed();
+
+        let peak_growth = baseline
+            .zip(status_bytes("VmHWM:"))
+            .map(|(baseline, peak)| peak.saturating_sub(baseline));
+
+        Ok(Run {
+            digest,
+            elapsed,
+            peak_growth,
+        })
+    }
+}
+
+/// Resets the peak resident set size to the current one, and returns it in bytes
+fn reset_peak_rss() -> Option<u64> {
+    fs::write("/proc/self/clear_refs", "5").ok()?;
+
+    status_bytes("VmRSS:")
+}
+
+/// Value of a `/proc/self/status` field given in kB, in bytes
+fn status_bytes(field: &str) -> Option<u64> {
+    let status = fs::read_to_string("/proc/self/status").ok()?;
+    let value = status.lines().find_map(|line| line.strip_prefix(field))?;
+    let kib: u64 = value.trim().strip_suffix("kB")?.trim().parse().ok()?;
+
+    Some(kib * 1024)
+}
+
+fn format_size(bytes: usize) -> String {
+    if bytes >= 1 << 20 && bytes % (1 << 20) == 0 {
+        format!("{} MiB", bytes >> 20)
+    } else if bytes >= 1 << 10 && bytes % (1 << 10) == 0 {
+        format!("{} KiB", bytes >> 10)
+    } else {
+        format!("{bytes} B")
+    }
+}
+
+/// File of pseudo-random bytes, removed when dropped
+struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    fn generate(size: u64) -> Result<TempFile, Error> {
+        let path = temp::temp_path("bench");
+
+        // created before writing, so a partial file is removed too
+        let temp_file = TempFile { path };
+        let io_error = |err| Error::io(temp_file.path.display(), err);
+
+        let mut file = io::BufWriter::new(File::create(&temp_file.path).map_err(io_error)?);
+        let mut state: u64 = 0x9e3779b97f4a7c15;
+        let mut chunk = vec![0u8; 1 << 20];
+        let mut left = size;
+
+        while left > 0 {
+            // xorshift64, random enough for data no one looks at
+            for bytes in chunk.chunks_exact_mut(8) {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                bytes.copy_from_slice(&state.to_le_bytes());
+            }
+
+            let len = left.min(chunk.len() as u64) as usize;
+            file.write_all(&chunk[..len]).map_err(io_error)?;
+            left -= len as u64;
+        }
+
+        file.flush().map_err(io_error)?;
+
+        Ok(temp_file)
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<BenchCommand, &'static str> {
+        BenchCommand::parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let command_good = BenchCommand {
+            file: None,
+            size: DEFAULT_SIZE,
+            buffer_sizes: DEFAULT_BUFFER_SIZES.to_vec(),
+        };
+        assert_eq!(parse(&[]), Ok(command_good));
+
+        let command = parse(&["--size", "1G", "--buffer-size=8K", "--buffer-size", "2M"]).unwrap();
+        assert_eq!(command.size, 1 << 30);
+        assert_eq!(command.buffer_sizes, vec![8 << 10, 2 << 20]);
+
+        assert_eq!(parse(&["file"]).unwrap().file, Some(String::from("file")));
+
+        assert!(parse(&["--size", "1G", "file"]).is_err());
+        assert!(parse(&["a", "b"]).is_err());
+        assert!(parse(&["--buffer-size", "0"]).is_err());
+        assert!(parse(&["--jobs", "2"]).is_err());
+    }
+
+    #[test]
+    fn format_size_test() {
+        assert_eq!(format_size(4096), "4 KiB");
+        assert_eq!(format_size(3 << 20), "3 MiB");
+        assert_eq!(format_size(1500), "1500 B");
+        assert_eq!(format_size(1), "1 B");
+    }
+
+    #[test]
+    fn every_strategy_agrees() {
+        let temp_file = TempFile::generate(100_000).unwrap();
+        assert_eq!(fs::metadata(&temp_file.path).unwrap().len(), 100_000);
+
+        let command = BenchCommand {
+            file: None,
+            size: 100_000,
+            buffer_sizes: vec![1, 100, 4096],
+        };
+
+        let mut out = Vec::new();
+        command.bench(&temp_file.path, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let digest = sha_256_scratch::sha_256(fs::read(&temp_file.path).unwrap());
+        assert!(out
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with(&format!("sha256 {digest})")));
+
+        let strategies: Vec<&str> = out
+            .lines()
+            .skip(2)
+            .map(|line| line[..18].trim_end())
+            .collect();
+        let mut strategies_good = vec!["read", "buffered 1 B", "buffered 100 B", "buffered 4 KiB"];
+        if cfg!(feature = "mmap") {
+            strategies_good.push("mmap");
+        }
+        assert_eq!(strategies, strategies_good);
+
+        let path = temp_file.path.clone();
+        drop(temp_file);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,586 @@
+//! Persistent hash cache (`--cache PATH`), so that unchanged files aren't read again.
+//!
+//! Entries are keyed by algorithm, device and inode, and only hit when the file size,
+//! modification time and status change time (both in nanoseconds) are still the ones
+//! recorded when it was hashed. A file whose metadata changed while it was being hashed
+//! is not cached.
+//!
+//! Each entry also records the path it was hashed from. When the cache is saved, entries
+//! whose path doesn't exist anymore, or now refers to another inode, are evicted.
+//!
+//! Like git's index, entries of files modified no earlier than the cache is saved are left out:
+//! a write happening within the same timestamp tick would leave the metadata unchanged, so such
+//! "racily clean" entries can't be trusted. The save time is read from the temporary cache file
+//! itself, so it has the clock and granularity of the filesystem.
+//!
+//! Some tools restore modification times after writing a file, and status change times can
+//! be reset too. `--verify-cache` rehashes a random sample of entries to find such stale ones.
+//!
+//! The cache file is line based, written to a temporary file (`<cache>.tmp.<pid>`) and then renamed:
+//!
+//! ~~~text
+//! # sha256sum_from_scratch cache v1
+//! <algorithm> <device> <inode> <size> <mtime_ns> <ctime_ns> <hex digest> <absolute path>
+//! ~~~
+//!
+//! Paths are escaped like in manifests, and their bytes which aren't valid UTF-8 are written as
+//! `\xHH`, so that any Unix path is stored exactly and found again on the next run.
+
+use crate::algorithm::Hasher;
+use crate::error::Error;
+use crate::input;
+use crate::manifest::escape_path;
+use sha_256_scratch::Digest;
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const HEADER: &str = "# sha256sum_from_scratch cache v1";
+
+/// Metadata a cache entry is only valid for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileKey {
+    pub device: u64,
+    pub inode: u64,
+    pub size: u64,
+    pub mtime_ns: i128,
+    pub ctime_ns: i128,
+}
+
+impl FileKey {
+    #[cfg(unix)]
+    pub fn of(path: &Path) -> io::Result<FileKey> {
+        use std::os::unix::fs::MetadataExt;
+
+        let metadata = fs::metadata(path)?;
+
+        Ok(FileKey {
+            device: metadata.dev(),
+            inode: metadata.ino(),
+            size: metadata.size(),
+            mtime_ns: metadata.mtime() as i128 * 1_000_000_000 + metadata.mtime_nsec() as i128,
+            ctime_ns: metadata.ctime() as i128 * 1_000_000_000 + metadata.ctime_nsec() as i128,
+        })
+    }
+
+    #[cfg(not(unix))]
+    pub fn of(_path: &Path) -> io::Result<FileKey> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the hash cache needs device and inode numbers, only available on Unix",
+        ))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct CacheEntry {
+    key: FileKey,
+    digest: Digest,
+    path: PathBuf,
+}
+
+pub struct HashCache {
+    file: PathBuf,
+    entries: HashMap<(String, u64, u64), CacheEntry>, // by (algorithm, device, inode)
+}
+
+impl HashCache {
+    /// Loads the cache stored in `file`, or an empty one if it doesn't exist yet
+    pub fn load(file: &Path) -> Result<HashCache, Error> {
+        let mut cache = HashCache {
+            file: file.to_path_buf(),
+            entries: HashMap::new(),
+        };
+
+        let content = match fs::read_to_string(file) {
+            Ok(content) => content,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(cache),
+            Err(err) => return Err(Error::io(file.display(), err)),
+        };
+
+        let mut lines = content.lines().enumerate();
+
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(Error::malformed(file.display(), "not a hash cache"));
+        }
+
+        for (index, line) in lines {
+            let (algorithm, entry) = parse_entry(line).ok_or_else(|| {
+                Error::malformed(file.display(), format!("line {} is invalid", index + 1))
+            })?;
+
+            cache.insert(&algorithm, entry);
+        }
+
+        Ok(cache)
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    /// Evicts entries of deleted or replaced files, then writes the cache back,
+    /// without the entries of files modified too recently to be trusted
+    pub fn save(&mut self) -> io::Result<()> {
+        self.entries.retain(|_, entry| {
+            FileKey::of(&entry.path)
+                .is_ok_and(|key| key.device == entry.key.device && key.inode == entry.key.inode)
+        });
+
+        let mut sorted: Vec<_> = self.entries.iter().collect();
+        sorted.sort_by(|a, b| a.1.path.cmp(&b.1.path).then(a.0.cmp(b.0)));
+
+        // appended rather than replacing the extension, so caches named `a.x` and `a.y` don't share it
+        let mut tmp_file = OsString::from(&self.file);
+        tmp_file.push(format!(".tmp.{}", std::process::id()));
+        let tmp_file = PathBuf::from(tmp_file);
+
+        let file = fs::File::create(&tmp_file)?;
+        let saved_ns = file
+            .metadata()?
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_nanos() as i128);
+        let mut out = io::BufWriter::new(file);
+
+        writeln!(out, "{HEADER}")?;
+
+        let trusted = sorted
+            .into_iter()
+            .filter(|(_, entry)| entry.key.mtime_ns < saved_ns);
+
+        for ((algorithm, _, _), entry) in trusted {
+            let key = entry.key;
+
+            writeln!(
+                out,
+                "{algorithm} {} {} {} {} {} {} {}",
+                key.device,
+                key.inode,
+                key.size,
+                key.mtime_ns,
+                key.ctime_ns,
+                entry.digest,
+                path_field(&entry.path)
+            )?;
+        }
+
+        out.flush()?;
+        drop(out);
+
+        fs::rename(tmp_file, &self.file)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the cached digest, if the file metadata didn't change since it was hashed
+    pub fn get(&self, algorithm: &str, key: &FileKey) -> Option<Digest> {
+        let entry = self
+            .entries
+            .get(&(algorithm.to_string(), key.device, key.inode))?;
+
+        (entry.key == *key).then_some(entry.digest)
+    }
+
+    fn insert(&mut self, algorithm: &str, entry: CacheEntry) {
+        let id = (algorithm.to_string(), entry.key.device, entry.key.inode);
+        self.entries.insert(id, entry);
+    }
+
+    /// Rehashes up to `sample_size` random entries, evicts and returns the paths of stale ones
+    pub fn verify_sample(
+        &mut self,
+        sample_size: usize,
+        hasher: impl Fn(&str) -> Option<Hasher>,
+    ) -> (usize, Vec<PathBuf>) {
+        let mut ids: Vec<_> = self.entries.keys().cloned().collect();
+        ids.sort();
+
+        let sample = random_sample(&mut ids, sample_size, random_seed());
+        let mut stale = Vec::new();
+
+        for id in sample.iter() {
+            let entry = &self.entries[id];
+
+            let is_valid = match (FileKey::of(&entry.path), hasher(&id.0)) {
+                (Ok(key), Some(hasher)) if key == entry.key => {
+                    input::hash_file(&entry.path, hasher)
+                        .is_ok_and(|(digest, _)| digest == entry.digest)
+                }
+                _ => false,
+            };
+
+            if !is_valid {
+                stale.push(entry.path.clone());
+                self.entries.remove(id);
+            }
+        }
+
+        (sample.len(), stale)
+    }
+}
+
+/// Hashes the file at `path`, unless an up-to-date digest is in the cache
+pub fn hash_file_cached(
+    path: &Path,
+    algorithm: &str,
+    hasher: Hasher,
+    cache: &Mutex<HashCache>,
+) -> io::Result<(Digest, u64)> {
+    let before = FileKey::of(path)?;
+
+    if let Some(digest) = lock(cache).get(algorithm, &before) {
+        return Ok((digest, before.size));
+    }
+
+    let (digest, size) = input::hash_file(path, hasher)?;
+
+    // the file could have been modified while it was being read
+    if FileKey::of(path)? == before {
+        let entry = CacheEntry {
+            key: before,
+            digest,
+            path: fs::canonicalize(path)?,
+        };
+
+        lock(cache).insert(algorithm, entry);
+    }
+
+    Ok((digest, size))
+}
+
+fn lock(cache: &Mutex<HashCache>) -> std::sync::MutexGuard<'_, HashCache> {
+    // a worker panicking while holding the lock can't leave the map half-updated
+    cache
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+fn parse_entry(line: &str) -> Option<(String, CacheEntry)> {
+    let mut fields = line.splitn(8, ' ');
+
+    let algorithm = fields.next()?.to_string();
+    let key = FileKey {
+        device: fields.next()?.parse().ok()?,
+        inode: fields.next()?.parse().ok()?,
+        size: fields.next()?.parse().ok()?,
+        mtime_ns: fields.next()?.parse().ok()?,
+        ctime_ns: fields.next()?.parse().ok()?,
+    };
+    let digest = Digest::from_hex(fields.next()?)?;
+    let path = parse_path_field(fields.next()?)?;
+
+    Some((algorithm, CacheEntry { key, digest, path }))
+}
+
+/// Path as written in the cache, with the bytes that aren't valid UTF-8 escaped as `\xHH`
+fn path_field(path: &Path) -> String {
+    let bytes = path_bytes(path);
+    let mut rest = &bytes[..];
+    let mut field = String::new();
+
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                field.push_str(&escape_path(valid));
+                return field;
+            }
+            Err(err) => {
+                let (valid, invalid) = rest.split_at(err.valid_up_to());
+                let invalid_len = err.error_len().unwrap_or(invalid.len());
+
+                field.push_str(&escape_path(&String::from_utf8_lossy(valid)));
+
+                for byte in &invalid[..invalid_len] {
+                    field.push_str(&format!("\\x{byte:02x}"));
+                }
+
+                rest = &invalid[invalid_len..];
+            }
+        }
+    }
+}
+
+fn parse_path_field(field: &str) -> Option<PathBuf> {
+    let mut bytes = Vec::new();
+    let mut chars = field.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue;
+        }
+
+        match chars.next()? {
+            '\\' => bytes.push(b'\\'),
+            'n' => bytes.push(b'\n'),
+            'r' => bytes.push(b'\r'),
+            'x' => {
+                let digits: String = chars.by_ref().take(2).collect();
+
+                if digits.len() != 2 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                    return None;
+                }
+
+                bytes.push(u8::from_str_radix(&digits, 16).ok()?);
+            }
+            _ => return None,
+        }
+    }
+
+    path_from_bytes(bytes)
+}
+
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Cow::Borrowed(path.as_os_str().as_bytes())
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Cow<'_, [u8]> {
+    // the cache isn't available outside Unix, see FileKey::of
+    Cow::Owned(path.to_string_lossy().into_owned().into_bytes())
+}
+
+#[cfg(unix)]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    use std::os::unix::ffi::OsStringExt;
+
+    Some(PathBuf::from(std::ffi::OsString::from_vec(bytes)))
+}
+
+#[cfg(not(unix))]
+fn path_from_bytes(bytes: Vec<u8>) -> Option<PathBuf> {
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+fn random_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_nanos() as u64);
+
+    nanos ^ ((std::process::id() as u64) << 32) | 1
+}
+
+/// Picks `count` items with a partial Fisher-Yates shuffle driven by a xorshift generator
+fn random_sample<T>(items: &mut [T], count: usize, mut seed: u64) -> &[T] {
+    let count = count.min(items.len());
+
+    for i in 0..count {
+        seed ^= seed << 13;
+        seed ^= seed >> 7;
+        seed ^= seed << 17;
+
+        let j = i + (seed % (items.len() - i) as u64) as usize;
+        items.swap(i, j);
+    }
+
+    &items[..count]
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::algorithm::Algorithm;
+    use crate::test_util::new_test_dir;
+    use std::time::Duration;
+
+    ////////////////// functions for setting up unit tests scenarios
+    /// Writes the file with a modification time in the past, so it isn't racily clean when saved
+    fn write_old_file(path: &Path, content: &str) {
+        fs::write(path, content).unwrap();
+
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(SystemTime::now() - Duration::from_secs(60))
+            .unwrap();
+    }
+
+    ////////////////// unit tests
+    #[test]
+    fn cache_hit_and_reload() {
+        let dir = new_test_dir("cache_hit");
+        let file = dir.join("hi");
+        write_old_file(&file, "hi");
+
+        let cache = Mutex::new(HashCache::load(&dir.join("cache")).unwrap());
+        let (digest, size) =
+            hash_file_cached(&file, "sha256", Algorithm::Sha256.hasher(), &cache).unwrap();
+
+        let mut cache = cache.into_inner().unwrap();
+        cache.save().unwrap();
+
+        let reloaded = HashCache::load(&dir.join("cache")).unwrap();
+        let key = FileKey::of(&file).unwrap();
+
+        assert_eq!(size, 2);
+        assert_eq!(reloaded.get("sha256", &key), Some(digest));
+        assert_eq!(reloaded.get("sha224", &key), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn cache_miss_when_metadata_changes() {
+        let dir = new_test_dir("cache_miss");
+        let file = dir.join("file");
+        fs::write(&file, "a").unwrap();
+
+        let cache = Mutex::new(HashCache::load(&dir.join("cache")).unwrap());
+        hash_file_cached(&file, "sha256", Algorithm::Sha256.hasher(), &cache).unwrap();
+
+        fs::write(&file, "ab").unwrap();
+        let key = FileKey::of(&file).unwrap();
+
+        assert_eq!(lock(&cache).get("sha256", &key), None);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn save_evicts_deleted_files() {
+        let dir = new_test_dir("cache_evict");
+        let (kept, deleted) = (dir.join("kept"), dir.join("deleted"));
+        write_old_file(&kept, "kept");
+        write_old_file(&deleted, "deleted");
+
+        let cache = Mutex::new(HashCache::load(&dir.join("cache")).unwrap());
+        hash_file_cached(&kept, "sha256", Algorithm::Sha256.hasher(), &cache).unwrap();
+        hash_file_cached(&deleted, "sha256", Algorithm::Sha256.hasher(), &cache).unwrap();
+
+        fs::remove_file(&deleted).unwrap();
+
+        let mut cache = cache.into_inner().unwrap();
+        cache.save().unwrap();
+
+        assert_eq!(HashCache::load(&dir.join("cache")).unwrap().len(), 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn save_skips_racily_clean_entries() {
+        let dir = new_test_dir("cache_racy");
+        let (old, fresh) = (dir.join("old"), dir.join("fresh"));
+        write_old_file(&old, "old");
+
+        // modified in the future, so never strictly older than the save time
+        fs::write(&fresh, "fresh").unwrap();
+        let file = fs::File::options().write(true).open(&fresh).unwrap();
+        file.set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+
+        let cache = Mutex::new(HashCache::load(&dir.join("cache")).unwrap());
+        hash_file_cached(&old, "sha256", Algorithm::Sha256.hasher(), &cache).unwrap();
+        hash_file_cached(&fresh, "sha256", Algorithm::Sha256.hasher(), &cache).unwrap();
+
+        let mut cache = cache.into_inner().unwrap();
+        cache.save().unwrap();
+
+        let reloaded = HashCache::load(&dir.join("cache")).unwrap();
+        assert_eq!(reloaded.len(), 1);
+        assert!(reloaded
+            .get("sha256", &FileKey::of(&old).unwrap())
+            .is_some());
+
+        // the temporary file was renamed over the cache, nothing else is left behind
+        let names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .collect();
+        assert_eq!(names.len(), 3);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn verify_sample_finds_stale_entries() {
+        let dir = new_test_dir("cache_verify");
+        let file = dir.join("file");
+        fs::write(&file, "original").unwrap();
+
+        let cache = Mutex::new(HashCache::load(&dir.join("cache")).unwrap());
+        hash_file_cached(&file, "sha256", Algorithm::Sha256.hasher(), &cache).unwrap();
+        let mut cache = cache.into_inner().unwrap();
+
+        // same metadata, other content: as if the timestamps had been restored
+        let key = FileKey::of(&file).unwrap();
+        let id = (String::from("sha256"), key.device, key.inode);
+        cache.entries.get_mut(&id).unwrap().digest = Digest::from_hex(&"0".repeat(64)).unwrap();
+
+        let (nb_checked, stale) = cache.verify_sample(10, |algorithm| {
+            Algorithm::from_name(algorithm).map(|algorithm| algorithm.hasher())
+        });
+
+        assert_eq!(nb_checked, 1);
+        assert_eq!(stale, vec![fs::canonicalize(&file).unwrap()]);
+        assert!(cache.is_empty());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn non_utf8_path_is_kept() {
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = new_test_dir("cache_non_utf8");
+        let file = dir.join(std::ffi::OsStr::from_bytes(b"caf\xe9\nlatin-1"));
+        write_old_file(&file, "hi");
+
+        let cache = Mutex::new(HashCache::load(&dir.join("cache")).unwrap());
+        let (digest, _) =
+            hash_file_cached(&file, "sha256", Algorithm::Sha256.hasher(), &cache).unwrap();
+
+        let mut cache = cache.into_inner().unwrap();
+        cache.save().unwrap();
+
+        let content = fs::read_to_string(dir.join("cache")).unwrap();
+        assert!(content.ends_with("/caf\\xe9\\nlatin-1\n"), "{content}");
+
+        // an entry whose path didn't round-trip would be evicted by the second save
+        let mut reloaded = HashCache::load(&dir.join("cache")).unwrap();
+        reloaded.save().unwrap();
+        assert_eq!(reloaded.len(), 1);
+        assert_eq!(
+            reloaded.get("sha256", &FileKey::of(&file).unwrap()),
+            Some(digest)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn path_fields() {
+        let path = Path::new("/a\\b\rc\u{e9}");
+        assert_eq!(path_field(path), "/a\\\\b\\rc\u{e9}");
+        assert_eq!(parse_path_field(&path_field(path)).unwrap(), path);
+
+        assert_eq!(parse_path_field("/a\\x4"), None);
+        assert_eq!(parse_path_field("/a\\x+f"), None);
+        assert_eq!(parse_path_field("/a\\q"), None);
+    }
+
+    #[test]
+    fn random_sample_test() {
+        let mut items: Vec<u32> = (0..100).collect();
+        let mut sample = random_sample(&mut items, 10, 42).to_vec();
+
+        sample.sort();
+        sample.dedup();
+
+        assert_eq!(sample.len(), 10);
+        assert_eq!(random_sample(&mut items, 1000, 42).len(), 100);
+    }
+}
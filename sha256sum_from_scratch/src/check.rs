@@ -0,0 +1,263 @@
+//! `--check` mode: reads checksum lists and verifies the files they reference.
+//!
+//! Two line formats are recognised:
+//! - GNU: `<hex digest>  <path>` (or `<hex digest> *<path>`), hashed with the selected algorithm
+//! - BSD-tagged: `<TAG> (<path>) = <hex digest>`, hashed with the algorithm named by the tag
+//!
+//! In HMAC mode, digests are HMAC-SHA256 tags computed with the given key,
+//! and BSD-tagged lines must start with `HMAC-SHA256`.
+
+use crate::algorithm::{Algorithm, Hasher};
+use crate::error::Error;
+use crate::input;
+use crate::pool;
+use sha_256_scratch::Digest;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+
+#[derive(Debug, PartialEq)]
+pub struct ChecksumLine {
+    pub algorithm: Algorithm,
+    pub expected: String, // lowercase hex digest
+    pub path: String,
+}
+
+/// Parses one line of a checksum list, GNU lines being hashed with `default`
+pub fn parse_line(line: &str, default: Algorithm, hmac: bool) -> Option<ChecksumLine> {
+    parse_bsd_line(line, hmac).or_else(|| parse_gnu_line(line, default))
+}
+
+fn parse_bsd_line(line: &str, hmac: bool) -> Option<ChecksumLine> {
+    let (tag, rest) = line.split_once(" (")?;
+    let (path, expected) = rest.rsplit_once(") = ")?;
+
+    let algorithm = if hmac {
+        Some(Algorithm::Sha256).filter(|_| tag == "HMAC-SHA256")?
+    } else {
+        Algorithm::from_bsd_tag(tag)?
+    };
+
+    checksum_line(algorithm, expected, path)
+}
+
+fn parse_gnu_line(line: &str, algorithm: Algorithm) -> Option<ChecksumLine> {
+    let (expected, rest) = line.split_once(' ')?;
+
+    // a space for text mode, a star for binary mode: both are hashed the same way
+    let path = rest.strip_prefix(' ').or_else(|| rest.strip_prefix('*'))?;
+
+    checksum_line(algorithm, expected, path)
+}
+
+fn checksum_line(algorithm: Algorithm, expected: &str, path: &str) -> Option<ChecksumLine> {
+    let is_valid_hex = expected.len() == 2 * algorithm.digest_len()
+        && expected.chars().all(|c| c.is_ascii_hexdigit());
+
+    if !is_valid_hex || path.is_empty() {
+        return None;
+    }
+
+    Some(ChecksumLine {
+        algorithm,
+        expected: expected.to_ascii_lowercase(),
+        path: path.to_string(),
+    })
+}
+
+/// Whether `digest` is the `expected` hex digest, compared in constant time since
+/// HMAC tags are secret until they match
+fn matches(digest: &Digest, expected: &str) -> bool {
+    Digest::from_hex(expected).is_some_and(|expected| digest.ct_eq(&expected))
+}
+
+fn read_list(list: &str) -> io::Result<String> {
+    if list == "-" {
+        let mut content = String::new();
+        io::stdin().lock().read_to_string(&mut content)?;
+
+        Ok(content)
+    } else {
+        fs::read_to_string(list)
+    }
+}
+
+/// Verifies every file referenced by the checksum `lists`, `jobs` files at a time
+pub fn check(
+    lists: &[String],
+    algorithm: Algorithm,
+    hmac_key: Option<&[u8]>,
+    jobs: usize,
+) -> Result<(), Error> {
+    let mut lines = Vec::new();
+    let mut nb_malformed = 0;
+
+    for list in lists {
+        let content = read_list(list).map_err(|err| Error::io(list, err))?;
+
+        for line in content.lines().filter(|line| !line.trim().is_empty()) {
+            match parse_line(line, algorithm, hmac_key.is_some()) {
+                Some(line) => lines.push(line),
+                None => nb_malformed += 1,
+            }
+        }
+    }
+
+    let (mut nb_mismatched, mut nb_unreadable) = (0, 0);
+
+    pool::map_ordered(
+        &lines,
+        jobs,
+        |line| {
+            let hasher = Hasher::new(line.algorithm, hmac_key);
+            input::hash_file(Path::new(&line.path), hasher)
+        },
+        |line, digest| match digest {
+            Ok((digest, _)) if matches(&digest, &line.expected) => println!("{}: OK", line.path),
+            Ok(_) => {
+                println!("{}: FAILED", line.path);
+                nb_mismatched += 1;
+            }
+            Err(err) => {
+                eprintln!("{}: {err}", line.path);
+                println!("{}: FAILED open or read", line.path);
+                nb_unreadable += 1;
+            }
+        },
+    );
+
+    if nb_malformed > 0 {
+        eprintln!("WARNING: {nb_malformed} line(s) improperly formatted");
+    }
+    if nb_unreadable > 0 {
+        eprintln!("WARNING: {nb_unreadable} listed file(s) could not be read");
+    }
+    if nb_mismatched > 0 {
+        eprintln!("WARNING: {nb_mismatched} computed checksum(s) did NOT match");
+    }
+
+    if lines.is_empty() {
+        return Err(Error::malformed(
+            lists.join(", "),
+            "no properly formatted checksum lines found",
+        ));
+    }
+
+    if nb_mismatched > 0 || nb_unreadable > 0 {
+        return Err(Error::ChecksumMismatch {
+            mismatched: nb_mismatched,
+            unreadable: nb_unreadable,
+        });
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::new_test_dir;
+
+    const HI_SHA256: &str = "8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4";
+    const HI_SHA224: &str = "1a15bca3e4ed3ab82367c6c434f86c41040bdac577fa1c2de62c3be0";
+    const HI_SHA256D: &str = "bc4f48d7a8651dc97ae415f0b47a52ef1a2702098202392b88bc925f6e89ee17";
+
+    #[test]
+    fn parse_gnu_line_test() {
+        let line =
+            parse_line(&format!("{HI_SHA256}  some file"), Algorithm::Sha256, false).unwrap();
+        assert_eq!(line.algorithm, Algorithm::Sha256);
+        assert_eq!(line.expected, HI_SHA256);
+        assert_eq!(line.path, "some file");
+
+        let line = parse_line(&format!("{HI_SHA256} *binary"), Algorithm::Sha256, false).unwrap();
+        assert_eq!(line.path, "binary");
+
+        // the digest length must match the selected algorithm
+        assert!(parse_line(&format!("{HI_SHA256}  file"), Algorithm::Sha224, false).is_none());
+    }
+
+    #[test]
+    fn parse_bsd_line_test() {
+        let line = parse_line(
+            &format!("SHA224 (a (b)) = {HI_SHA224}"),
+            Algorithm::Sha256,
+            false,
+        );
+
+        let line_good = ChecksumLine {
+            algorithm: Algorithm::Sha224,
+            expected: String::from(HI_SHA224),
+            path: String::from("a (b)"),
+        };
+
+        assert_eq!(line, Some(line_good));
+
+        let line = parse_line(
+            &format!("SHA256D (file) = {HI_SHA256D}"),
+            Algorithm::Sha256,
+            false,
+        );
+        assert_eq!(line.unwrap().algorithm, Algorithm::Sha256d);
+
+        assert!(parse_line(
+            &format!("MD5 (file) = {HI_SHA224}"),
+            Algorithm::Sha256,
+            false
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn parse_hmac_lines() {
+        let line = parse_line(
+            &format!("HMAC-SHA256 (f) = {HI_SHA256}"),
+            Algorithm::Sha256,
+            true,
+        );
+        assert_eq!(line.unwrap().algorithm, Algorithm::Sha256);
+
+        // plain digests can't be verified in HMAC mode, and HMAC tags can't be in plain mode
+        assert!(parse_line(
+            &format!("SHA256 (f) = {HI_SHA256}"),
+            Algorithm::Sha256,
+            true
+        )
+        .is_none());
+        assert!(parse_line(
+            &format!("HMAC-SHA256 (f) = {HI_SHA256}"),
+            Algorithm::Sha256,
+            false
+        )
+        .is_none());
+    }
+
+    #[test]
+    fn check_sha256d_list() {
+        let dir = new_test_dir("check_sha256d");
+        let file = dir.join("hi");
+        fs::write(&file, "hi").unwrap();
+
+        let list = dir.join("list");
+        let path = file.display();
+        fs::write(&list, format!("SHA256D ({path}) = {HI_SHA256D}\n")).unwrap();
+        let lists = [list.display().to_string()];
+        assert!(check(&lists, Algorithm::Sha256, None, 1).is_ok());
+
+        // the tag picks the algorithm, so a SHA-256 digest under it doesn't match
+        fs::write(&list, format!("SHA256D ({path}) = {HI_SHA256}\n")).unwrap();
+        assert!(matches!(
+            check(&lists, Algorithm::Sha256, None, 1),
+            Err(Error::ChecksumMismatch { mismatched: 1, .. })
+        ));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parse_malformed_lines() {
+        assert!(parse_line("", Algorithm::Sha256, false).is_none());
+        assert!(parse_line("not a checksum line", Algorithm::Sha256, false).is_none());
+        assert!(parse_line(&format!("{HI_SHA256}  "), Algorithm::Sha256, false).is_none());
+    }
+}
@@ -0,0 +1,475 @@
+//! `dupes` subcommand: finds byte-identical regular files below directories.
+//!
+//! Reading every byte of every file is avoided by narrowing candidates in three passes:
+//! 1. files are grouped by size, and files with a unique size are dropped,
+//! 2. the remaining ones are grouped by the SHA-256 of their first 4 KiB,
+//! 3. only files still colliding (and bigger than 4 KiB) are fully hashed.
+//!
+//! Symbolic links are not followed and empty files are ignored, since removing them reclaims nothing.
+//! Directories given several times, or nested in another given directory, are only walked once.
+//!
+//! Paths which are hard links to the same inode hold the data once. In hardlink-aware mode
+//! (`--hardlink-aware`), only one of them is kept, so clusters and reclaimable space
+//! only account for actual copies.
+
+use crate::algorithm::Algorithm;
+use crate::error::Error;
+use crate::output::json_string;
+use crate::{input, parse_jobs, pool, split_option};
+use sha_256_scratch::Digest;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes hashed by the second pass
+pub const PREFIX_SIZE: u64 = 4096;
+
+#[derive(Debug, PartialEq)]
+pub struct DupesCommand {
+    pub dirs: Vec<String>,
+    pub json: bool,
+    pub hardlink_aware: bool,
+    pub jobs: usize,
+}
+
+impl DupesCommand {
+    /// Parses the arguments following `dupes`
+    pub fn parse_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<DupesCommand, &'static str> {
+        let mut dirs = Vec::new();
+        let mut json = false;
+        let mut hardlink_aware = false;
+        let mut jobs = pool::default_jobs();
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                dirs.push(arg);
+                continue;
+            }
+
+            let (name, inline_value) = split_option(&arg);
+
+            match name {
+                "--json" => json = true,
+                "--hardlink-aware" => hardlink_aware = true,
+                "-j" | "--jobs" => jobs = parse_jobs(inline_value, &mut args)?,
+                _ => return Err("Unknown dupes option"),
+            }
+        }
+
+        if dirs.is_empty() {
+            return Err("dupes expects at least one directory");
+        }
+
+        Ok(DupesCommand {
+            dirs,
+            json,
+            hardlink_aware,
+            jobs,
+        })
+    }
+
+    pub fn run(&self) -> Result<(), Error> {
+        let dirs: Vec<PathBuf> = self.dirs.iter().map(PathBuf::from).collect();
+        let dupes = Dupes::find(&dirs, self.hardlink_aware, self.jobs)?;
+
+        let mut out = io::stdout().lock();
+        if self.json {
+            dupes.write_json(&mut out)?;
+        } else {
+            dupes.write_human(&mut out)?;
+        }
+
+        for error in &dupes.errors {
+            eprintln!("{error}");
+        }
+
+        if !dupes.errors.is_empty() {
+            let nb_failed = dupes.errors.len();
+            return Err(Error::Failed(format!(
+                "{nb_failed} file(s) could not be read"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// Files with the same content
+#[derive(Debug, PartialEq)]
+pub struct Cluster {
+    pub size: u64,
+    pub digest: Digest,
+    pub paths: Vec<PathBuf>, // sorted
+}
+
+impl Cluster {
+    /// Bytes freed by keeping only one of the paths
+    pub fn reclaimable(&self) -> u64 {
+        self.size * (self.paths.len() as u64 - 1)
+    }
+}
+
+#[derive(Debug)]
+pub struct Dupes {
+    pub clusters: Vec<Cluster>, // most reclaimable space first
+    pub errors: Vec<String>,    // files which could not be read, they are left out
+}
+
+#[derive(Debug, Clone)]
+struct Candidate {
+    path: PathBuf,
+    size: u64,
+}
+
+impl Dupes {
+    /// Looks for identical files below `dirs`, hashing `jobs` files at a time
+    pub fn find(dirs: &[PathBuf], hardlink_aware: bool, jobs: usize) -> Result<Dupes, Error> {
+        let mut files = Vec::new();
+
+        for dir in distinct_roots(dirs)? {
+            collect_files(&dir, &mut files).map_err(|err| Error::io(dir.display(), err))?;
+        }
+
+        // sorted, so the path kept among hard links to the same inode is always the same
+        files.sort_by(|a, b| a.0.path.cmp(&b.0.path));
+
+        let mut seen_inodes = HashSet::new();
+        let candidates = files.into_iter().filter_map(|(candidate, inode)| {
+            let is_new_inode = match inode {
+                Some(inode) if hardlink_aware => seen_inodes.insert(inode),
+                _ => true,
+            };
+
+            (candidate.size > 0 && is_new_inode).then_some(candidate)
+        });
+
+        let mut by_size: BTreeMap<u64, Vec<Candidate>> = BTreeMap::new();
+        for candidate in candidates {
+            by_size.entry(candidate.size).or_default().push(candidate);
+        }
+
+        let mut errors = Vec::new();
+
+        let same_size = by_size
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .collect();
+        let same_prefix = split_by_digest(same_size, jobs, &mut errors, |path| {
+            input::hash_reader(
+                File::open(path)?.take(PREFIX_SIZE),
+                Algorithm::Sha256.hasher(),
+            )
+        });
+
+        // the prefix digest of files no bigger than the prefix is already their full digest
+        let (mut identical, to_hash): (Vec<_>, Vec<_>) = same_prefix
+            .into_iter()
+            .partition(|(_, group)| group[0].size <= PREFIX_SIZE);
+
+        let to_hash = to_hash.into_iter().map(|(_, group)| group).collect();
+        identical.extend(split_by_digest(to_hash, jobs, &mut errors, |path| {
+            input::hash_file(path, Algorithm::Sha256.hasher())
+        }));
+
+        let mut clusters: Vec<Cluster> = identical
+            .into_iter()
+            .map(|(digest, group)| {
+                let mut paths: Vec<PathBuf> = group.iter().map(|file| file.path.clone()).collect();
+                paths.sort();
+
+                Cluster {
+                    size: group[0].size,
+                    digest,
+                    paths,
+                }
+            })
+            .collect();
+
+        clusters.sort_by(|a, b| {
+            b.reclaimable()
+                .cmp(&a.reclaimable())
+                .then_with(|| a.paths.cmp(&b.paths))
+        });
+
+        Ok(Dupes { clusters, errors })
+    }
+
+    pub fn reclaimable(&self) -> u64 {
+        self.clusters.iter().map(Cluster::reclaimable).sum()
+    }
+
+    pub fn write_human(&self, out: &mut impl Write) -> io::Result<()> {
+        for cluster in &self.clusters {
+            writeln!(
+                out,
+                "{} ({} copies of {} bytes, {} bytes reclaimable)",
+                cluster.digest,
+                cluster.paths.len(),
+                cluster.size,
+                cluster.reclaimable()
+            )?;
+
+            for path in &cluster.paths {
+                writeln!(out, "  {}", path.display())?;
+            }
+
+            writeln!(out)?;
+        }
+
+        writeln!(
+            out,
+            "{} clusters of identical files, {} bytes reclaimable",
+            self.clusters.len(),
+            self.reclaimable()
+        )
+    }
+
+    pub fn write_json(&self, out: &mut impl Write) -> io::Result<()> {
+        let clusters: Vec<String> = self
+            .clusters
+            .iter()
+            .map(|cluster| {
+                let paths: Vec<String> = cluster
+                    .paths
+                    .iter()
+                    .map(|path| json_string(&path.to_string_lossy()))
+                    .collect();
+
+                format!(
+                    "{{\"sha256\":{},\"size\":{},\"reclaimable\":{},\"paths\":[{}]}}",
+                    json_string(&cluster.digest.to_hex()),
+                    cluster.size,
+                    cluster.reclaimable(),
+                    paths.join(",")
+                )
+            })
+            .collect();
+
+        writeln!(
+            out,
+            "{{\"clusters\":[{}],\"reclaimable\":{}}}",
+            clusters.join(","),
+            self.reclaimable()
+        )
+    }
+}
+
+/// Splits each group of candidates by the digest `hash` gives, dropping the candidates left alone
+fn split_by_digest(
+    groups: Vec<Vec<Candidate>>,
+    jobs: usize,
+    errors: &mut Vec<String>,
+    hash: impl Fn(&Path) -> io::Result<(Digest, u64)> + Sync,
+) -> Vec<(Digest, Vec<Candidate>)> {
+    let files: Vec<(usize, Candidate)> = groups
+        .into_iter()
+        .enumerate()
+        .flat_map(|(index, group)| group.into_iter().map(move |file| (index, file)))
+        .collect();
+
+    let mut split: HashMap<(usize, Digest), Vec<Candidate>> = HashMap::new();
+
+    pool::map_ordered(
+        &files,
+        jobs,
+        |(_, file)| hash(&file.path),
+        |(index, file), result| match result {
+            Ok((digest, _)) => split
+                .entry((*index, digest))
+                .or_default()
+                .push(file.clone()),
+            Err(err) => errors.push(format!("{}: {err}", file.path.display())),
+        },
+    );
+
+    split
+        .into_iter()
+        .filter(|(_, group)| group.len() > 1)
+        .map(|((_, digest), group)| (digest, group))
+        .collect()
+}
+
+/// Drops the directories which are given twice or nested in another one
+fn distinct_roots(dirs: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut canonical_dirs = Vec::new();
+
+    for dir in dirs {
+        let canonical = fs::canonicalize(dir)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", dir.display())))?;
+        canonical_dirs.push((canonical, dir));
+    }
+
+    let roots = canonical_dirs
+        .iter()
+        .enumerate()
+        .filter(|(i, (canonical, _))| {
+            !canonical_dirs.iter().enumerate().any(|(j, (other, _))| {
+                // of two identical directories, the first one given is kept
+                canonical.starts_with(other) && (canonical != other || j < *i)
+            })
+        })
+        .map(|(_, (_, dir))| dir.to_path_buf())
+        .collect();
+
+    Ok(roots)
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<(Candidate, Option<(u64, u64)>)>) -> io::Result<()> {
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let file_type = dir_entry.file_type()?;
+
+        if file_type.is_dir() {
+            collect_files(&dir_entry.path(), files)?;
+        } else if file_type.is_file() {
+            let metadata = dir_entry.metadata()?;
+            let candidate = Candidate {
+                path: dir_entry.path(),
+                size: metadata.len(),
+            };
+
+            files.push((candidate, inode(&metadata)));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn inode(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn inode(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::new_test_dir;
+    use std::slice;
+
+    ////////////////// functions for setting up unit tests scenarios
+    fn cluster_paths(dupes: &Dupes, dir: &Path) -> Vec<Vec<String>> {
+        let relative = |path: &PathBuf| {
+            let path = path.strip_prefix(dir).unwrap().to_string_lossy();
+            path.replace('\\', "/")
+        };
+
+        dupes
+            .clusters
+            .iter()
+            .map(|cluster| cluster.paths.iter().map(relative).collect())
+            .collect()
+    }
+
+    ////////////////// unit tests
+    #[test]
+    fn find_dupes() {
+        let dir = new_test_dir("dupes_find");
+        let big = vec![b'a'; 3 * PREFIX_SIZE as usize];
+        let mut big_other_end = big.clone();
+        *big_other_end.last_mut().unwrap() = b'b';
+
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("big1"), &big).unwrap();
+        fs::write(dir.join("sub").join("big2"), &big).unwrap();
+        fs::write(dir.join("big_other_end"), &big_other_end).unwrap();
+        fs::write(dir.join("hi1"), "hi").unwrap();
+        fs::write(dir.join("hi2"), "hi").unwrap();
+        fs::write(dir.join("ho"), "ho").unwrap();
+        fs::write(dir.join("empty1"), "").unwrap();
+        fs::write(dir.join("empty2"), "").unwrap();
+
+        let dupes = Dupes::find(slice::from_ref(&dir), false, 2).unwrap();
+
+        assert_eq!(
+            cluster_paths(&dupes, &dir),
+            vec![vec!["big1", "sub/big2"], vec!["hi1", "hi2"]]
+        );
+        assert_eq!(
+            dupes.clusters[1].digest.to_hex(),
+            "8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4"
+        );
+        assert_eq!(dupes.reclaimable(), 3 * PREFIX_SIZE + 2);
+        assert!(dupes.errors.is_empty());
+
+        // a nested directory isn't walked twice
+        let dupes = Dupes::find(&[dir.join("sub"), dir.clone()], false, 2).unwrap();
+        assert_eq!(dupes.reclaimable(), 3 * PREFIX_SIZE + 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn hardlink_aware() {
+        let dir = new_test_dir("dupes_hardlinks");
+        fs::write(dir.join("a"), "hi").unwrap();
+        fs::hard_link(dir.join("a"), dir.join("a_link")).unwrap();
+        fs::write(dir.join("b"), "ho").unwrap();
+        fs::hard_link(dir.join("b"), dir.join("b_link")).unwrap();
+        fs::write(dir.join("b_copy"), "ho").unwrap();
+
+        let dupes = Dupes::find(slice::from_ref(&dir), false, 1).unwrap();
+        assert_eq!(
+            cluster_paths(&dupes, &dir),
+            vec![vec!["b", "b_copy", "b_link"], vec!["a", "a_link"]]
+        );
+
+        let dupes = Dupes::find(slice::from_ref(&dir), true, 1).unwrap();
+        assert_eq!(cluster_paths(&dupes, &dir), vec![vec!["b", "b_copy"]]);
+        assert_eq!(dupes.reclaimable(), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn write_json() {
+        let dupes = Dupes {
+            clusters: vec![Cluster {
+                size: 2,
+                digest: Digest::from_hex(
+                    "8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4",
+                )
+                .unwrap(),
+                paths: vec![PathBuf::from("a"), PathBuf::from("b\"")],
+            }],
+            errors: Vec::new(),
+        };
+
+        let mut out = Vec::new();
+        dupes.write_json(&mut out).unwrap();
+
+        let output_good = concat!(
+            "{\"clusters\":[{\"sha256\":",
+            "\"8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4\",",
+            "\"size\":2,\"reclaimable\":2,\"paths\":[\"a\",\"b\\\"\"]}],\"reclaimable\":2}\n"
+        );
+        assert_eq!(String::from_utf8(out).unwrap(), output_good);
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let args = ["a", "--json", "b", "--hardlink-aware", "-j2"].map(String::from);
+        let command = DupesCommand::parse_args(args.into_iter()).unwrap();
+
+        let command_good = DupesCommand {
+            dirs: vec![String::from("a"), String::from("b")],
+            json: true,
+            hardlink_aware: true,
+            jobs: 2,
+        };
+        assert_eq!(command, command_good);
+
+        assert!(DupesCommand::parse_args(std::iter::empty()).is_err());
+        assert!(DupesCommand::parse_args(["--force", "a"].map(String::from).into_iter()).is_err());
+    }
+}
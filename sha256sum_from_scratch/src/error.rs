@@ -0,0 +1,108 @@
+//! Errors of the command line interface, all reported by `main` on stderr.
+
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum Error {
+    /// invalid command line arguments
+    Usage(&'static str),
+    /// I/O error on a path, or on the standard streams when there is no path
+    Io {
+        path: Option<String>,
+        source: io::Error,
+    },
+    /// a file isn't in the format it should be (checksum list, manifest, piece list, cache)
+    Malformed { path: String, reason: String },
+    /// files whose digest didn't match their checksum list, or couldn't be read to check it
+    ChecksumMismatch {
+        mismatched: usize,
+        unreadable: usize,
+    },
+    /// the HMAC key couldn't be loaded
+    InvalidKey(String),
+    /// error from the hash library
+    Hash(sha_256_scratch::Error),
+    /// some inputs failed, each of them has already been reported
+    Failed(String),
+}
+
+impl Error {
+    pub fn io(path: impl fmt::Display, source: io::Error) -> Error {
+        Error::Io {
+            path: Some(path.to_string()),
+            source,
+        }
+    }
+
+    pub fn malformed(path: impl fmt::Display, reason: impl Into<String>) -> Error {
+        Error::Malformed {
+            path: path.to_string(),
+            reason: reason.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Usage(message) => f.write_str(message),
+            Error::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{path}: {source}"),
+            Error::Io { path: None, source } => write!(f, "{source}"),
+            Error::Malformed { path, reason } => write!(f, "{path}: {reason}"),
+            Error::ChecksumMismatch {
+                mismatched,
+                unreadable,
+            } => write!(
+                f,
+                "{mismatched} computed checksum(s) did NOT match, {unreadable} file(s) could not be read"
+            ),
+            Error::InvalidKey(message) => write!(f, "invalid HMAC key: {message}"),
+            Error::Hash(err) => write!(f, "{err}"),
+            Error::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Hash(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+/// Errors of the standard streams, other I/O errors are given their path with `Error::io`
+impl From<io::Error> for Error {
+    fn from(source: io::Error) -> Error {
+        Error::Io { path: None, source }
+    }
+}
+
+impl From<sha_256_scratch::Error> for Error {
+    fn from(err: sha_256_scratch::Error) -> Error {
+        Error::Hash(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let err = Error::io("file", io::Error::new(io::ErrorKind::NotFound, "not found"));
+        assert_eq!(err.to_string(), "file: not found");
+
+        let err = Error::malformed("list", "line 3 is invalid");
+        assert_eq!(err.to_string(), "list: line 3 is invalid");
+
+        let err = Error::from(sha_256_scratch::Error::ZeroPieceSize);
+        assert_eq!(err.to_string(), "the piece size must not be 0");
+    }
+}
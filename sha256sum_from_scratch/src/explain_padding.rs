@@ -0,0 +1,271 @@
+//! `explain-padding` subcommand: how `pre_process` pads a message into 64-byte blocks,
+//! see [`sha_256_scratch::padding`].
+//!
+//! `explain-padding [--all] (--string <TEXT> | <FILE>)` prints the original length, where the
+//! 0x80 byte is, how many zero bytes were added and why, and the big-endian 64 bits length
+//! field, then every block holding padding as an annotated hex dump. Blocks made of message
+//! bytes only are summarized in one line unless `--all` is given.
+
+use crate::error::Error;
+use crate::output::hex;
+use crate::trace::MessageInput;
+use crate::{option_value, split_option};
+use sha_256_scratch::padding::{PaddingLayout, Region};
+use std::io::{self, Write};
+
+const BYTES_PER_ROW: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub struct ExplainPaddingCommand {
+    pub input: MessageInput,
+    pub all: bool,
+}
+
+impl ExplainPaddingCommand {
+    /// Parses the arguments following `explain-padding`
+    pub fn parse_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<ExplainPaddingCommand, &'static str> {
+        let mut inputs = Vec::new();
+        let mut all = false;
+
+        while let Some(arg) = args.next() {
+            if arg == "-" || !arg.starts_with('-') {
+                inputs.push(MessageInput::File(arg));
+                continue;
+            }
+
+            let (name, inline_value) = split_option(&arg);
+
+            match name {
+                "--all" => all = true,
+                "--string" => inputs.push(MessageInput::String(
+                    option_value(inline_value, &mut args).ok_or("No string has been provided")?,
+                )),
+                _ => return Err("Unknown explain-padding option"),
+            }
+        }
+
+        let [input] = <[MessageInput; 1]>::try_from(inputs)
+            .map_err(|_| "explain-padding expects one file or --string")?;
+
+        Ok(ExplainPaddingCommand { input, all })
+    }
+
+    pub fn run(&self) -> Result<(), Error> {
+        let layout = PaddingLayout::new(self.input.read()?);
+        let mut out = io::stdout().lock();
+
+        write_summary(&layout, &mut out)?;
+        writeln!(out)?;
+        write_blocks(&layout, self.all, &mut out)?;
+
+        Ok(())
+    }
+}
+
+pub fn write_summary(layout: &PaddingLayout, out: &mut impl Write) -> io::Result<()> {
+    let marker_block = layout.marker_offset / 64;
+    let marker_in_block = layout.marker_offset % 64;
+
+    writeln!(
+        out,
+        "message: {} bytes = {} bits",
+        layout.msg_len, layout.length_bits
+    )?;
+    writeln!(
+        out,
+        "0x80 byte: offset {} (byte {marker_in_block} of block {marker_block}), a single 1 bit right after the message",
+        layout.marker_offset
+    )?;
+
+    if layout.needs_extra_block() {
+        writeln!(
+            out,
+            "zero bytes: {}, only {} bytes are left in block {marker_block} after the 0x80 byte, too few for the 8 bytes length field: \
+             zeros fill it and the next block up to byte 56",
+            layout.zero_bytes,
+            63 - marker_in_block
+        )?;
+    } else {
+        writeln!(
+            out,
+            "zero bytes: {}, up to byte 56 of block {marker_block} (448 bits modulo 512), leaving exactly 8 bytes for the length field",
+            layout.zero_bytes
+        )?;
+    }
+
+    writeln!(
+        out,
+        "length field: offset {}, {} = {} bits as a big-endian 64 bits integer",
+        layout.length_offset,
+        hex(&layout.padded[layout.length_offset..]),
+        layout.length_bits
+    )?;
+    writeln!(
+        out,
+        "padded message: {} bytes, {} block(s) of 64 bytes",
+        layout.padded.len(),
+        layout.nb_blocks()
+    )
+}
+
+pub fn write_blocks(layout: &PaddingLayout, all: bool, out: &mut impl Write) -> io::Result<()> {
+    // blocks before the one holding the 0x80 byte are made of message bytes only
+    let first_padding_block = layout.marker_offset / 64;
+
+    let first_shown = match all || first_padding_block == 0 {
+        true => 0,
+        false => {
+            writeln!(
+                out,
+                "blocks 0 to {}: message bytes only ({} bytes)",
+                first_padding_block - 1,
+                first_padding_block * 64
+            )?;
+            writeln!(out)?;
+
+            first_padding_block
+        }
+    };
+
+    for index in first_shown..layout.nb_blocks() {
+        if let Ok(block) = layout.block(index) {
+            write_block(layout, index, block, out)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn write_block(
+    layout: &PaddingLayout,
+    index: usize,
+    block: &[u8],
+    out: &mut impl Write,
+) -> io::Result<()> {
+    writeln!(out, "block {index}")?;
+
+    let block_offset = index * 64;
+
+    for (row, bytes) in block.chunks(BYTES_PER_ROW).enumerate() {
+        let row_offset = block_offset + row * BYTES_PER_ROW;
+        let bytes: Vec<String> = bytes.iter().map(|byte| hex(&[*byte])).collect();
+
+        writeln!(
+            out,
+            "  {row_offset:08x}  {}  {}",
+            bytes.join(" "),
+            annotation(layout, row_offset)
+        )?;
+    }
+
+    writeln!(out)
+}
+
+/// Parts of the padded message a row is made of, with their number of bytes
+fn annotation(layout: &PaddingLayout, row_offset: usize) -> String {
+    let mut parts: Vec<(Region, usize)> = Vec::new();
+
+    for offset in row_offset..row_offset + BYTES_PER_ROW {
+        let region = layout.region(offset);
+
+        match parts.last_mut() {
+            Some((last, count)) if *last == region => *count += 1,
+            _ => parts.push((region, 1)),
+        }
+    }
+
+    let parts: Vec<String> = parts
+        .into_iter()
+        .map(|(region, count)| match region {
+            Region::Message => format!("message ({count})"),
+            Region::Marker => String::from("0x80"),
+            Region::Zero => format!("zeros ({count})"),
+            Region::Length => format!("length ({count})"),
+        })
+        .collect();
+
+    parts.join(" | ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ////////////////// functions for setting up unit tests scenarios
+    fn summary(msg_len: usize) -> String {
+        let mut out = Vec::new();
+        write_summary(&PaddingLayout::new(vec![b'a'; msg_len]), &mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    fn blocks(msg_len: usize, all: bool) -> String {
+        let mut out = Vec::new();
+        write_blocks(&PaddingLayout::new(vec![b'a'; msg_len]), all, &mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    ////////////////// unit tests
+    #[test]
+    fn parse_args_test() {
+        let parse = |args: &[&str]| {
+            ExplainPaddingCommand::parse_args(args.iter().map(|arg| arg.to_string()))
+        };
+
+        let command_good = ExplainPaddingCommand {
+            input: MessageInput::File(String::from("file")),
+            all: true,
+        };
+        assert_eq!(parse(&["file", "--all"]), Ok(command_good));
+
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--string", "abc", "file"]).is_err());
+    }
+
+    #[test]
+    fn summary_in_one_block() {
+        let summary = summary(3);
+
+        assert!(summary.starts_with("message: 3 bytes = 24 bits\n"));
+        assert!(summary.contains("0x80 byte: offset 3 (byte 3 of block 0)"));
+        assert!(summary.contains("zero bytes: 52, up to byte 56 of block 0"));
+        assert!(summary.contains("length field: offset 56, 0000000000000018 = 24 bits"));
+        assert!(summary.ends_with("padded message: 64 bytes, 1 block(s) of 64 bytes\n"));
+    }
+
+    #[test]
+    fn summary_with_extra_block() {
+        let summary = summary(60);
+
+        assert!(summary.contains("zero bytes: 59, only 3 bytes are left in block 0"));
+        assert!(summary.contains("length field: offset 120, 00000000000001e0 = 480 bits"));
+    }
+
+    #[test]
+    fn annotated_hex_dump() {
+        let dump = blocks(3, false);
+        let lines: Vec<&str> = dump.lines().collect();
+
+        assert_eq!(lines[0], "block 0");
+        assert_eq!(
+            lines[1],
+            "  00000000  61 61 61 80 00 00 00 00 00 00 00 00 00 00 00 00  message (3) | 0x80 | zeros (12)"
+        );
+        assert_eq!(
+            lines[4],
+            "  00000030  00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 18  zeros (8) | length (8)"
+        );
+    }
+
+    #[test]
+    fn message_only_blocks_summarized() {
+        let dump = blocks(200, false);
+
+        assert!(dump.starts_with("blocks 0 to 2: message bytes only (192 bytes)\n\nblock 3\n"));
+        assert!(blocks(200, true).starts_with("block 0\n"));
+        assert_eq!(blocks(200, true).matches("block ").count(), 4);
+    }
+}
@@ -0,0 +1,161 @@
+//! Minimal wrapper around the Linux inotify API, used by `--watch`.
+//!
+//! Only the system calls creating the instance and adding watches need `unsafe`:
+//! events are read through a regular [`File`] and decoded from bytes.
+
+use std::ffi::{CString, OsString};
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path::Path;
+
+pub use libc::{IN_CLOSE_WRITE, IN_MOVED_TO, IN_Q_OVERFLOW};
+
+/// Size of the `wd`, `mask`, `cookie` and `len` fields of `struct inotify_event`
+const EVENT_HEADER_SIZE: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub struct Event {
+    pub wd: i32,
+    pub mask: u32,
+    pub name: Option<OsString>, // only set for events about an entry of a watched directory
+}
+
+pub struct Inotify {
+    file: File,
+}
+
+impl Inotify {
+    pub fn new() -> io::Result<Inotify> {
+        // SAFETY: inotify_init1 takes no pointer, it returns a new file descriptor or -1
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC) };
+
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // SAFETY: the descriptor has just been created, so nothing else owns or closes it
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+
+        Ok(Inotify {
+            file: File::from(fd),
+        })
+    }
+
+    /// Watches `path` for the events of `mask`, returns the watch descriptor found in its events
+    pub fn add_watch(&self, path: &Path, mask: u32) -> io::Result<i32> {
+        let c_path = CString::new(path.as_os_str().as_bytes())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "path contains a NUL byte"))?;
+
+        // SAFETY: the descriptor stays open as long as self, and c_path is a NUL-terminated
+        // string outliving the call, which doesn't keep the pointer
+        let wd = unsafe { libc::inotify_add_watch(self.file.as_raw_fd(), c_path.as_ptr(), mask) };
+
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(wd)
+    }
+
+    /// Blocks until events are available and returns them
+    pub fn read_events(&mut self) -> io::Result<Vec<Event>> {
+        // big enough for at least one event with a NAME_MAX long name
+        let mut buffer = [0u8; 16 * 1024];
+
+        loop {
+            match self.file.read(&mut buffer) {
+                Ok(read) => return Ok(parse_events(&buffer[..read])),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+fn parse_events(mut buffer: &[u8]) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    while buffer.len() >= EVENT_HEADER_SIZE {
+        let field = |index: usize| {
+            let bytes = [0, 1, 2, 3].map(|i| buffer[4 * index + i]);
+            u32::from_ne_bytes(bytes)
+        };
+
+        let end = buffer.len().min(EVENT_HEADER_SIZE + field(3) as usize);
+
+        // the name is padded with NUL bytes
+        let name = buffer[EVENT_HEADER_SIZE..end]
+            .split(|byte| *byte == 0)
+            .next()
+            .unwrap_or_default();
+
+        events.push(Event {
+            wd: field(0) as i32,
+            mask: field(1),
+            name: (!name.is_empty()).then(|| OsString::from_vec(name.to_vec())),
+        });
+
+        buffer = &buffer[end..];
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::new_test_dir;
+    use std::fs;
+
+    fn event_bytes(wd: i32, mask: u32, name: &str, len: u32) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend(wd.to_ne_bytes());
+        bytes.extend(mask.to_ne_bytes());
+        bytes.extend(0u32.to_ne_bytes());
+        bytes.extend(len.to_ne_bytes());
+        bytes.extend(name.as_bytes());
+        bytes.resize(EVENT_HEADER_SIZE + len as usize, 0);
+
+        bytes
+    }
+
+    #[test]
+    fn parse_events_test() {
+        let mut buffer = event_bytes(1, IN_CLOSE_WRITE, "file", 16);
+        buffer.extend(event_bytes(2, IN_Q_OVERFLOW, "", 0));
+
+        let events_good = vec![
+            Event {
+                wd: 1,
+                mask: IN_CLOSE_WRITE,
+                name: Some(OsString::from("file")),
+            },
+            Event {
+                wd: 2,
+                mask: IN_Q_OVERFLOW,
+                name: None,
+            },
+        ];
+
+        assert_eq!(parse_events(&buffer), events_good);
+    }
+
+    #[test]
+    fn close_write_event() {
+        let dir = new_test_dir("inotify");
+
+        let mut inotify = Inotify::new().unwrap();
+        let wd = inotify.add_watch(&dir, IN_CLOSE_WRITE).unwrap();
+
+        fs::write(dir.join("file"), "hi").unwrap();
+
+        let events = inotify.read_events().unwrap();
+        assert_eq!(events[0].wd, wd);
+        assert_eq!(events[0].name, Some(OsString::from("file")));
+        assert_ne!(events[0].mask & IN_CLOSE_WRITE, 0);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,197 @@
+//! Streaming file input: files are read through a fixed-size buffer (`--buffer-size`,
+//! 64 KiB by default) and given piece by piece to the incremental hasher, so they are never
+//! fully loaded in memory. `--mmap` hashes them from a memory mapping instead, see [`crate::mmap`].
+//!
+//! Only a byte range of an input can be hashed (`--offset`/`--length`): files are seeked
+//! to the start of the range, while the standard input is read and discarded up to it.
+
+use crate::algorithm::Hasher;
+use sha_256_scratch::Digest;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+pub const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Returns the digest of the file content and its size in bytes
+pub fn hash_file(path: &Path, hasher: Hasher) -> io::Result<(Digest, u64)> {
+    hash_reader(File::open(path)?, hasher)
+}
+
+/// Returns the digest of everything `reader` yields and its size in bytes
+pub fn hash_reader(reader: impl Read, hasher: Hasher) -> io::Result<(Digest, u64)> {
+    hash_buffered(reader, hasher, BUFFER_SIZE)
+}
+
+/// Same as `hash_reader`, reading through a buffer of `buffer_size` bytes
+pub fn hash_buffered(
+    mut reader: impl Read,
+    mut hasher: Hasher,
+    buffer_size: usize,
+) -> io::Result<(Digest, u64)> {
+    let mut buffer = vec![0u8; buffer_size.max(1)];
+    let mut size = 0;
+
+    loop {
+        let read = match reader.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        };
+
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok((hasher.finalize(), size))
+}
+
+/// Part of an input to hash, the whole input by default
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct ByteRange {
+    pub offset: u64,
+    pub length: Option<u64>, // up to the end of the input when not set
+}
+
+impl ByteRange {
+    pub fn is_whole_input(&self) -> bool {
+        *self == ByteRange::default()
+    }
+}
+
+/// Opens the file positioned at the start of the range, checking the range fits in it
+pub fn open_range(path: &Path, range: ByteRange) -> io::Result<File> {
+    let mut file = File::open(path)?;
+
+    if range.is_whole_input() {
+        return Ok(file);
+    }
+
+    let metadata = file.metadata()?;
+
+    // the size of special files, such as block devices, isn't known from their metadata
+    if metadata.is_file() {
+        range_len(range, metadata.len())?;
+    }
+
+    file.seek(SeekFrom::Start(range.offset))?;
+
+    Ok(file)
+}
+
+/// Number of bytes of the range in a file of `file_len` bytes, checking the range fits in it
+pub fn range_len(range: ByteRange, file_len: u64) -> io::Result<u64> {
+    let end = range
+        .offset
+        .checked_add(range.length.unwrap_or(0))
+        .ok_or_else(|| range_error(String::from("the range end overflows")))?;
+
+    if end > file_len {
+        return Err(range_error(format!(
+            "the range ends at byte {end}, past the end of the file ({file_len} bytes)"
+        )));
+    }
+
+    Ok(range.length.unwrap_or(file_len - range.offset))
+}
+
+/// Reads and discards the bytes of `reader` before the range, for inputs which can't be seeked
+pub fn skip_to_range(reader: &mut impl Read, range: ByteRange) -> io::Result<()> {
+    let skipped = io::copy(&mut reader.take(range.offset), &mut io::sink())?;
+
+    if skipped < range.offset {
+        return Err(range_error(format!(
+            "the range starts at byte {}, past the end of the input ({skipped} bytes)",
+            range.offset
+        )));
+    }
+
+    Ok(())
+}
+
+/// Hashes the range from the current position of `reader` with `hash` (`hash_buffered` or
+/// `pipeline::hash_pipelined`), failing if the input ends before the range does
+pub fn hash_range<R: Read>(
+    reader: R,
+    range: ByteRange,
+    hash: impl FnOnce(io::Take<R>) -> io::Result<(Digest, u64)>,
+) -> io::Result<(Digest, u64)> {
+    // the whole input is a range ending after any input could
+    let (digest, size) = hash(reader.take(range.length.unwrap_or(u64::MAX)))?;
+
+    if let Some(length) = range.length.filter(|&length| size < length) {
+        return Err(range_error(format!(
+            "the input ended after {size} of the {length} bytes of the range"
+        )));
+    }
+
+    Ok((digest, size))
+}
+
+fn range_error(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::Algorithm;
+    use crate::temp::temp_path;
+    use std::fs;
+
+    fn hash_bytes(content: &[u8]) -> Digest {
+        hash_reader(content, Algorithm::Sha256.hasher()).unwrap().0
+    }
+
+    #[test]
+    fn hash_file_range() {
+        let file = temp_path("input");
+        fs::write(&file, "0123456789").unwrap();
+
+        let hash = |offset, length| {
+            let range = ByteRange { offset, length };
+            let reader = open_range(&file, range)?;
+
+            hash_range(reader, range, |reader| {
+                hash_reader(reader, Algorithm::Sha256.hasher())
+            })
+        };
+
+        assert_eq!(hash(2, Some(3)).unwrap(), (hash_bytes(b"234"), 3));
+        assert_eq!(hash(8, None).unwrap(), (hash_bytes(b"89"), 2));
+        assert_eq!(hash(10, Some(0)).unwrap(), (hash_bytes(b""), 0));
+
+        let err = hash(8, Some(3)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the range ends at byte 11, past the end of the file (10 bytes)"
+        );
+        assert!(hash(11, None).is_err());
+        assert!(hash(u64::MAX, Some(1)).is_err());
+
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn hash_stream_range() {
+        let hash = |offset, length| {
+            let range = ByteRange { offset, length };
+            let mut reader = &b"0123456789"[..];
+            skip_to_range(&mut reader, range)?;
+
+            // a tiny buffer makes the range end in the middle of a read
+            hash_range(reader, range, |reader| {
+                hash_buffered(reader, Algorithm::Sha256.hasher(), 2)
+            })
+        };
+
+        assert_eq!(hash(2, Some(3)).unwrap(), (hash_bytes(b"234"), 3));
+        assert_eq!(hash(0, None).unwrap(), (hash_bytes(b"0123456789"), 10));
+        assert!(hash(11, None).is_err());
+        assert_eq!(
+            hash(8, Some(3)).unwrap_err().to_string(),
+            "the input ended after 2 of the 3 bytes of the range"
+        );
+    }
+}
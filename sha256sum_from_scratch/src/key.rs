@@ -0,0 +1,77 @@
+//! Where the HMAC key comes from.
+//!
+//! The key is never accepted as a plain command line argument,
+//! since arguments can be read by any user through the process list.
+//! Once loaded, it is wiped from memory on drop like the library wipes its key pads.
+
+use crate::error::Error;
+use std::env;
+use std::fs;
+use std::ops::Deref;
+
+#[derive(Debug, PartialEq)]
+pub enum HmacKeySource {
+    /// file whose whole content is the key, used as is (including any trailing newline)
+    File(String),
+    /// environment variable holding the key
+    Env(String),
+}
+
+/// HMAC key bytes, overwritten with zeros when dropped
+pub struct HmacKey(Vec<u8>);
+
+impl Deref for HmacKey {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl Drop for HmacKey {
+    fn drop(&mut self) {
+        sha_256_scratch::wipe(&mut self.0);
+    }
+}
+
+impl HmacKeySource {
+    pub fn load(&self) -> Result<HmacKey, Error> {
+        let key = match self {
+            HmacKeySource::File(path) => fs::read(path).map_err(|err| Error::io(path, err))?,
+            HmacKeySource::Env(var) => match env::var_os(var) {
+                Some(key) => os_string_bytes(key)?,
+                None => {
+                    return Err(Error::InvalidKey(format!(
+                        "environment variable {var} is not set"
+                    )))
+                }
+            },
+        };
+
+        // wrapped before any check, so an invalid key is wiped too
+        let key = HmacKey(key);
+
+        if key.is_empty() {
+            return Err(Error::InvalidKey(String::from("the HMAC key is empty")));
+        }
+
+        Ok(key)
+    }
+}
+
+#[cfg(unix)]
+fn os_string_bytes(value: std::ffi::OsString) -> Result<Vec<u8>, Error> {
+    use std::os::unix::ffi::OsStringExt;
+
+    Ok(value.into_vec())
+}
+
+#[cfg(not(unix))]
+fn os_string_bytes(value: std::ffi::OsString) -> Result<Vec<u8>, Error> {
+    match value.into_string() {
+        Ok(value) => Ok(value.into_bytes()),
+        Err(_) => Err(Error::InvalidKey(String::from(
+            "the HMAC key environment variable is not valid UTF-8",
+        ))),
+    }
+}
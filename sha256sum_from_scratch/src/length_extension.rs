@@ -0,0 +1,234 @@
+//! `length-extension` subcommand: a length-extension forgery shown end to end, to teach why
+//! SHA256(secret || msg) is not a MAC.
+//!
+//! A local demo verifier draws a secret and signs the message with SHA256(secret || msg).
+//! Knowing only the message, its tag and the secret length, a forged message ending with the
+//! appended suffix and its tag are computed with [`sha_256_scratch::extension`], then given
+//! to the verifier, which accepts them. The same forgery against an HMAC-SHA256 verifier
+//! is rejected.
+
+use crate::error::Error;
+use crate::output::hex;
+use crate::{option_value, split_option};
+use sha_256_scratch::extension;
+use sha_256_scratch::{Digest, Hmac, Sha256};
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{self, Write};
+
+const DEFAULT_MESSAGE: &str = "user=alice&role=user";
+const DEFAULT_APPEND: &str = "&role=admin";
+const DEFAULT_SECRET_LENGTH: usize = 16;
+
+#[derive(Debug, PartialEq)]
+pub struct LengthExtensionCommand {
+    pub message: String,
+    pub append: String,
+    pub secret_length: usize,
+}
+
+impl LengthExtensionCommand {
+    /// Parses the arguments following `length-extension`
+    pub fn parse_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<LengthExtensionCommand, &'static str> {
+        let mut command = LengthExtensionCommand {
+            message: String::from(DEFAULT_MESSAGE),
+            append: String::from(DEFAULT_APPEND),
+            secret_length: DEFAULT_SECRET_LENGTH,
+        };
+
+        while let Some(arg) = args.next() {
+            let (name, inline_value) = split_option(&arg);
+
+            match name {
+                "--message" => {
+                    command.message = option_value(inline_value, &mut args)
+                        .ok_or("No message has been provided")?;
+                }
+                "--append" => {
+                    command.append = option_value(inline_value, &mut args)
+                        .ok_or("No suffix to append has been provided")?;
+                }
+                "--secret-length" => {
+                    command.secret_length = match option_value(inline_value, &mut args)
+                        .map(|value| value.parse::<usize>())
+                    {
+                        Some(Ok(length)) if length > 0 => length,
+                        _ => return Err("The secret length must be a positive integer"),
+                    };
+                }
+                _ => return Err("Unknown length-extension option"),
+            }
+        }
+
+        Ok(command)
+    }
+
+    /// Runs the demo, returns whether the SHA256(secret || msg) verifier accepted the forgery
+    pub fn run(&self) -> Result<bool, Error> {
+        let verifier = DemoVerifier::new(self.secret_length);
+
+        self.demo(&verifier, &mut io::stdout().lock())
+    }
+
+    fn demo(&self, verifier: &DemoVerifier, out: &mut impl Write) -> Result<bool, Error> {
+        let message = self.message.as_bytes();
+        let append = self.append.as_bytes();
+
+        let tag = verifier.sign(message);
+
+        writeln!(
+            out,
+            "verifier secret: {} random bytes, unknown to the attacker",
+            self.secret_length
+        )?;
+        writeln!(out, "message: {}", message.escape_ascii())?;
+        writeln!(out, "tag = SHA256(secret || message): {tag}")?;
+        writeln!(out)?;
+
+        // the tag is a SHA-256 digest, so it can always be extended
+        let forgery = extension::forge(&tag, self.secret_length, message, append)?;
+
+        writeln!(
+            out,
+            "glue padding ({} bytes): {}",
+            forgery.glue_padding.len(),
+            hex(&forgery.glue_padding)
+        )?;
+        writeln!(out, "forged message: {}", forgery.msg.escape_ascii())?;
+        writeln!(out, "forged tag: {}", forgery.digest)?;
+        writeln!(out)?;
+
+        let accepted = verifier.verify(&forgery.msg, &forgery.digest);
+        writeln!(
+            out,
+            "SHA256(secret || message) verifier: forgery {}",
+            verdict(accepted)
+        )?;
+
+        let hmac_tag = verifier.hmac_sign(message);
+        let hmac_forgery = extension::forge(&hmac_tag, self.secret_length, message, append)?;
+        writeln!(
+            out,
+            "HMAC-SHA256 verifier: forgery {}",
+            verdict(verifier.hmac_verify(&hmac_forgery.msg, &hmac_forgery.digest))
+        )?;
+
+        Ok(accepted)
+    }
+}
+
+/// Holds the secret and checks tags, the attacker only sees messages and tags
+pub struct DemoVerifier {
+    secret: Vec<u8>,
+}
+
+impl DemoVerifier {
+    pub fn new(secret_length: usize) -> DemoVerifier {
+        DemoVerifier {
+            secret: random_bytes(secret_length),
+        }
+    }
+
+    /// SHA256(secret || msg), the insecure construction
+    pub fn sign(&self, msg: &[u8]) -> Digest {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.secret);
+        hasher.update(msg);
+
+        hasher.finalize()
+    }
+
+    pub fn verify(&self, msg: &[u8], tag: &Digest) -> bool {
+        self.sign(msg).ct_eq(tag)
+    }
+
+    pub fn hmac_sign(&self, msg: &[u8]) -> Digest {
+        let mut hmac = Hmac::new(&self.secret);
+        hmac.update(msg);
+
+        hmac.finalize()
+    }
+
+    pub fn hmac_verify(&self, msg: &[u8], tag: &Digest) -> bool {
+        self.hmac_sign(msg).ct_eq(tag)
+    }
+}
+
+/// Unpredictable enough for a demo secret, not for real keys
+fn random_bytes(len: usize) -> Vec<u8> {
+    let state = RandomState::new();
+    let mut bytes = Vec::with_capacity(len + 8);
+
+    while bytes.len() < len {
+        let mut hasher = state.build_hasher();
+        hasher.write_usize(bytes.len());
+        bytes.extend(hasher.finish().to_le_bytes());
+    }
+
+    bytes.truncate(len);
+    bytes
+}
+
+fn verdict(accepted: bool) -> &'static str {
+    match accepted {
+        true => "ACCEPTED",
+        false => "REJECTED",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<LengthExtensionCommand, &'static str> {
+        LengthExtensionCommand::parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let command_good = LengthExtensionCommand {
+            message: String::from("amount=10"),
+            append: String::from("0"),
+            secret_length: 32,
+        };
+        assert_eq!(
+            parse(&[
+                "--message",
+                "amount=10",
+                "--append=0",
+                "--secret-length",
+                "32"
+            ]),
+            Ok(command_good)
+        );
+
+        assert_eq!(parse(&[]).unwrap().secret_length, DEFAULT_SECRET_LENGTH);
+        assert!(parse(&["--secret-length", "0"]).is_err());
+        assert!(parse(&["--message"]).is_err());
+        assert!(parse(&["message"]).is_err());
+    }
+
+    #[test]
+    fn forgery_accepted_only_without_hmac() {
+        let command = parse(&["--secret-length", "24"]).unwrap();
+        let verifier = DemoVerifier::new(24);
+
+        let mut out = Vec::new();
+        assert!(command.demo(&verifier, &mut out).unwrap());
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.contains("glue padding (20 bytes): 80000000"));
+        assert!(out.contains("forged message: user=alice&role=user\\x80\\x00"));
+        assert!(out.contains("SHA256(secret || message) verifier: forgery ACCEPTED"));
+        assert!(out.contains("HMAC-SHA256 verifier: forgery REJECTED"));
+    }
+
+    #[test]
+    fn random_secrets() {
+        assert_eq!(random_bytes(0), Vec::<u8>::new());
+        assert_eq!(random_bytes(13).len(), 13);
+        assert_ne!(random_bytes(32), random_bytes(32));
+    }
+}
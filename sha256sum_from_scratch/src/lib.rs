@@ -1,31 +1,887 @@
-#![forbid(unsafe_code)]
+//! Command line interface around the `sha_256_scratch` crate.
+//!
+//! Files are streamed through the incremental hasher of the selected algorithm (`-a/--algorithm`,
+//! inferred from the binary name by default) and hashed concurrently by a bounded worker pool
+//! (`-j/--jobs`). `-c/--check` verifies GNU or BSD-tagged checksum lists.
+//! With `--hmac-key-file` or `--hmac-key-env`, inputs are HMAC-SHA256'd instead of plain-hashed.
+//! Results are printed as GNU or BSD-tagged lines, or as records described in [`output`].
+//! `--cache PATH` skips files whose metadata didn't change since they were hashed, see [`cache`].
+//! `--watch` keeps rehashing files as they are written, see [`watch`].
+//! `--offset` and `--length` restrict hashing to a byte range of each input, see [`input`].
+//! `--buffer-size` sets the size of the read buffer, `--mmap` maps files instead, see [`mmap`].
+//! `--pipeline` reads inputs on another thread while they are hashed, see [`pipeline`].
+//! `--progress` and `--stats` report on stderr how much was hashed and how fast, see [`progress`].
+//!
+//! Besides per-file lines, `--tree <DIR>` prints one digest standing for a whole directory tree.
+//! The canonical encoding it is computed from is specified in [`tree`].
+//!
+//! Subcommands, given as first argument, provide other tools built on the hash functions:
+//! - `manifest create|diff`: directory snapshots and their differences, see [`manifest`]
+//! - `dupes`: clusters of identical files, see [`dupes`]
+//! - `pieces`: digests of fixed-size pieces of a file and their verification, see [`pieces`]
+//! - `length-extension`: a length-extension forgery against SHA256(secret || msg), see [`length_extension`]
+//! - `trace`: every intermediate value of the compression function, see [`trace`]
+//! - `explain-padding`: how a message is padded into blocks, see [`explain_padding`]
+//! - `bench`: throughput and memory of the file input strategies, see [`bench`]
 
-use std::env;
-use std::error::Error;
+// `forbid` can't be lifted by an inner `allow`, so the builds which need unsafe code (inotify
+// system calls on Linux, memory mappings with the mmap feature) only `deny` it, and allow it in
+// the module concerned, where every unsafe block has its SAFETY comment
+#![cfg_attr(not(any(target_os = "linux", feature = "mmap")), forbid(unsafe_code))]
+#![cfg_attr(any(target_os = "linux", feature = "mmap"), deny(unsafe_code))]
+
+pub mod algorithm;
+pub mod bench;
+pub mod cache;
+pub mod check;
+pub mod dupes;
+pub mod error;
+pub mod explain_padding;
+// system calls of the inotify API, which only exists on Linux
+#[cfg(target_os = "linux")]
+#[allow(unsafe_code)]
+mod inotify;
+pub mod input;
+pub mod key;
+pub mod length_extension;
+pub mod manifest;
+// memory mappings, only created with the mmap feature
+#[cfg_attr(feature = "mmap", allow(unsafe_code))]
+pub mod mmap;
+pub mod output;
+pub mod pieces;
+pub mod pipeline;
+pub mod pool;
+pub mod progress;
+pub mod size;
+mod temp;
+#[cfg(test)]
+mod test_util;
+pub mod trace;
+pub mod tree;
+pub mod watch;
+
+use algorithm::{Algorithm, Hasher};
+use bench::BenchCommand;
+use cache::HashCache;
+use dupes::DupesCommand;
+use error::Error;
+use explain_padding::ExplainPaddingCommand;
+use input::{ByteRange, BUFFER_SIZE};
+use key::HmacKeySource;
+use length_extension::LengthExtensionCommand;
+use manifest::ManifestCommand;
+use output::{Format, Record, RecordWriter};
+use pieces::PiecesCommand;
+use pipeline::PipelineStats;
+use progress::{Progress, ProgressReader, Stats};
+use sha_256_scratch::Digest;
 use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use trace::TraceCommand;
+
+/// What the binary is asked to do: hashing the given files, or running a subcommand
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Hash,
+    Manifest(ManifestCommand),
+    Dupes(DupesCommand),
+    Pieces(PiecesCommand),
+    LengthExtension(LengthExtensionCommand),
+    Trace(TraceCommand),
+    ExplainPadding(ExplainPaddingCommand),
+    Bench(BenchCommand),
+}
+
+impl Command {
+    /// Exit status when the command fails
+    pub fn failure_code(&self) -> u8 {
+        match self {
+            // 1 is kept to signal differences, like diff(1) does
+            Command::Manifest(ManifestCommand::Diff { .. }) => 2,
+            _ => 1,
+        }
+    }
+}
+
+const DEFAULT_VERIFY_SAMPLE: usize = 64;
 
 pub struct Config {
-    pub filename: String,
+    pub command: Command,
+    pub filenames: Vec<String>,
+    pub algorithm: Algorithm,
+    pub hmac_key: Option<HmacKeySource>,
+    pub tree: bool,
+    pub check: bool,
+    pub format: Format,
+    pub jobs: usize,
+    pub sort: bool,
+    pub cache: Option<String>,
+    pub verify_cache: Option<usize>, // sample size
+    pub watch: bool,
+    pub progress: bool,
+    pub stats: bool,
+    pub range: ByteRange,
+    pub buffer_size: usize,
+    pub mmap: bool,
+    pub pipeline: bool,
 }
 
 impl Config {
-    pub fn new(mut args: env::Args) -> Result<Config, &'static str> {
-        args.next(); // ignores application name
+    pub fn new(args: impl Iterator<Item = String>) -> Result<Config, Error> {
+        Config::from_args(args).map_err(Error::Usage)
+    }
+
+    fn from_args(args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
+        let mut args = args.peekable();
+
+        let mut algorithm = args
+            .next()
+            .and_then(|binary| Algorithm::from_binary_name(&binary))
+            .unwrap_or(Algorithm::Sha256);
+
+        let command = match args.peek().map(String::as_str) {
+            Some("manifest") => {
+                args.next();
+                Command::Manifest(ManifestCommand::parse_args(&mut args)?)
+            }
+            Some("dupes") => {
+                args.next();
+                Command::Dupes(DupesCommand::parse_args(&mut args)?)
+            }
+            Some("pieces") => {
+                args.next();
+                Command::Pieces(PiecesCommand::parse_args(&mut args)?)
+            }
+            Some("length-extension") => {
+                args.next();
+                Command::LengthExtension(LengthExtensionCommand::parse_args(&mut args)?)
+            }
+            Some("trace") => {
+                args.next();
+                Command::Trace(TraceCommand::parse_args(&mut args)?)
+            }
+            Some("explain-padding") => {
+                args.next();
+                Command::ExplainPadding(ExplainPaddingCommand::parse_args(&mut args)?)
+            }
+            Some("bench") => {
+                args.next();
+                Command::Bench(BenchCommand::parse_args(&mut args)?)
+            }
+            _ => Command::Hash,
+        };
+
+        if command != Command::Hash {
+            return Ok(Config::for_subcommand(command, algorithm));
+        }
+
+        let mut filenames = Vec::new();
+        let mut hmac_key = None;
+        let mut tree = false;
+        let mut check = false;
+        let mut format = None;
+        let mut jobs = pool::default_jobs();
+        let mut sort = false;
+        let mut cache = None;
+        let mut verify_cache = None;
+        let mut watch = false;
+        let mut progress = false;
+        let mut stats = false;
+        let mut range = ByteRange::default();
+        let mut buffer_size = None;
+        let mut mmap = false;
+        let mut pipeline = false;
+        let mut only_paths_left = false;
+
+        while let Some(arg) = args.next() {
+            if only_paths_left || !arg.starts_with('-') || arg == "-" {
+                filenames.push(arg);
+                continue;
+            }
+
+            let (name, inline_value) = split_option(&arg);
+
+            match name {
+                "--" => only_paths_left = true,
+                "--tree" => tree = true,
+                "-c" | "--check" => check = true,
+                "--tag" => format = Some(Format::Bsd),
+                "--format" => {
+                    let value = option_value(inline_value, &mut args)
+                        .ok_or("No output format has been provided")?;
+
+                    format = Some(Format::from_name(&value).ok_or("Unsupported output format")?);
+                }
+                "--sort" => sort = true,
+                "--watch" => watch = true,
+                "--progress" => progress = true,
+                "--stats" => stats = true,
+                "--offset" => {
+                    let value = option_value(inline_value, &mut args)
+                        .ok_or("No offset has been provided")?;
+
+                    range.offset = size::parse_size(&value).ok_or(
+                        "The offset must be a number of bytes, optionally followed by K, M, G, KiB, MiB or GiB",
+                    )?;
+                }
+                "--length" => {
+                    let value = option_value(inline_value, &mut args)
+                        .ok_or("No length has been provided")?;
+
+                    range.length = Some(size::parse_size(&value).ok_or(
+                        "The length must be a number of bytes, optionally followed by K, M, G, KiB, MiB or GiB",
+                    )?);
+                }
+                "--buffer-size" => buffer_size = Some(parse_buffer_size(inline_value, &mut args)?),
+                "--mmap" => {
+                    if !cfg!(feature = "mmap") {
+                        return Err("--mmap needs a build with the mmap feature");
+                    }
+
+                    mmap = true;
+                }
+                "--pipeline" => pipeline = true,
+                "--cache" => {
+                    cache = Some(
+                        option_value(inline_value, &mut args)
+                            .ok_or("No cache file has been provided")?,
+                    );
+                }
+                "--verify-cache" => {
+                    // the sample size is optional, so it can only be given inline
+                    verify_cache = match inline_value.map(str::parse) {
+                        None => Some(DEFAULT_VERIFY_SAMPLE),
+                        Some(Ok(sample_size)) if sample_size > 0 => Some(sample_size),
+                        Some(_) => return Err("The cache sample size must be a positive integer"),
+                    };
+                }
+                "-a" | "--algorithm" => {
+                    let value = option_value(inline_value, &mut args)
+                        .ok_or("No algorithm has been provided")?;
+
+                    algorithm = Algorithm::from_name(&value).ok_or("Unsupported algorithm")?;
+                }
+                "--hmac-key-file" | "--hmac-key-env" => {
+                    if hmac_key.is_some() {
+                        return Err("Only one HMAC key can be provided");
+                    }
+
+                    let value = option_value(inline_value, &mut args)
+                        .ok_or("No HMAC key file or variable has been provided")?;
+
+                    hmac_key = Some(match name {
+                        "--hmac-key-file" => HmacKeySource::File(value),
+                        _ => HmacKeySource::Env(value),
+                    });
+                }
+                "--hmac-key" => {
+                    return Err("The HMAC key can't be given as an argument, since it would be visible in the process list: use --hmac-key-file or --hmac-key-env");
+                }
+                "-j" | "--jobs" => jobs = parse_jobs(inline_value, &mut args)?,
+                _ => return Err("Unknown option"),
+            }
+        }
+
+        if verify_cache.is_some() {
+            if cache.is_none() {
+                return Err("--verify-cache needs the cache given by --cache");
+            }
+
+            if !filenames.is_empty() {
+                return Err("--verify-cache doesn't hash files");
+            }
+        } else if filenames.is_empty() {
+            return Err("No filename has been provided");
+        }
+
+        if cache.is_some() && (tree || check || hmac_key.is_some()) {
+            return Err("--cache can't be used with --tree, --check or in HMAC mode");
+        }
+
+        if tree && check {
+            return Err("--tree and --check can't be used together");
+        }
+
+        if check && format.is_some_and(|format| format != Format::Gnu) {
+            return Err("--check doesn't support output formats");
+        }
+
+        if tree && algorithm != Algorithm::Sha256 {
+            return Err("--tree only supports the sha256 algorithm");
+        }
+
+        if tree && hmac_key.is_some() {
+            return Err("--tree can't be used in HMAC mode");
+        }
+
+        if hmac_key.is_some() && algorithm != Algorithm::Sha256 {
+            return Err("HMAC mode only supports the sha256 algorithm");
+        }
+
+        if watch
+            && (tree
+                || check
+                || cache.is_some()
+                || format.is_some_and(|format| format != Format::Gnu))
+        {
+            return Err("--watch can't be used with --tree, --check, --cache or output formats");
+        }
+
+        if !range.is_whole_input() && (tree || check || cache.is_some()) {
+            return Err("--offset and --length can't be used with --tree, --check or --cache");
+        }
+
+        if (progress || stats) && (check || watch || verify_cache.is_some()) {
+            return Err(
+                "--progress and --stats can't be used with --check, --watch or --verify-cache",
+            );
+        }
+
+        if mmap && buffer_size.is_some() {
+            return Err("--mmap reads no buffer, --buffer-size can't be given");
+        }
+
+        if mmap && pipeline {
+            return Err("--mmap reads nothing to pipeline, --pipeline can't be given");
+        }
 
-        let filename = match args.next() {
-            Some(filename) => filename,
-            None => return Err("No filename has been provided"),
+        if (mmap || pipeline || buffer_size.is_some()) && (tree || check || cache.is_some()) {
+            return Err(
+                "--mmap, --pipeline and --buffer-size can't be used with --tree, --check or --cache",
+            );
+        }
+
+        if mmap && filenames.iter().any(|filename| filename == "-") {
+            return Err("--mmap can't map the standard input");
+        }
+
+        if watch && filenames.iter().any(|filename| filename == "-") {
+            return Err("--watch can't watch the standard input");
+        }
+
+        if sort {
+            filenames.sort();
+        }
+
+        // a single input is printed as its bare digest, like before several inputs were supported
+        let format = format.unwrap_or(match filenames.len() {
+            1 => Format::Bare,
+            _ => Format::Gnu,
+        });
+
+        Ok(Config {
+            command,
+            filenames,
+            algorithm,
+            hmac_key,
+            tree,
+            check,
+            format,
+            jobs,
+            sort,
+            cache,
+            verify_cache,
+            watch,
+            progress,
+            stats,
+            range,
+            buffer_size: buffer_size.unwrap_or(BUFFER_SIZE),
+            mmap,
+            pipeline,
+        })
+    }
+
+    fn for_subcommand(command: Command, algorithm: Algorithm) -> Config {
+        Config {
+            command,
+            filenames: Vec::new(),
+            algorithm,
+            hmac_key: None,
+            tree: false,
+            check: false,
+            format: Format::Gnu,
+            jobs: pool::default_jobs(),
+            sort: false,
+            cache: None,
+            verify_cache: None,
+            watch: false,
+            progress: false,
+            stats: false,
+            range: ByteRange::default(),
+            buffer_size: BUFFER_SIZE,
+            mmap: false,
+            pipeline: false,
+        }
+    }
+}
+
+/// Splits `--name=value` and `-jN` forms into the option name and its inline value
+pub(crate) fn split_option(arg: &str) -> (&str, Option<&str>) {
+    if arg.starts_with("--") {
+        match arg.split_once('=') {
+            Some((name, value)) => (name, Some(value)),
+            None => (arg, None),
+        }
+    } else if arg.len() > 2 && arg.is_char_boundary(2) {
+        (&arg[..2], Some(&arg[2..]))
+    } else {
+        (arg, None)
+    }
+}
+
+pub(crate) fn option_value(
+    inline_value: Option<&str>,
+    args: &mut impl Iterator<Item = String>,
+) -> Option<String> {
+    match inline_value {
+        Some(value) => Some(value.to_string()),
+        None => args.next(),
+    }
+}
+
+pub(crate) fn parse_jobs(
+    inline_value: Option<&str>,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<usize, &'static str> {
+    let value = option_value(inline_value, args).ok_or("No number of jobs has been provided")?;
+
+    match value.parse() {
+        Ok(jobs) if jobs > 0 => Ok(jobs),
+        _ => Err("The number of jobs must be a positive integer"),
+    }
+}
+
+pub(crate) fn parse_buffer_size(
+    inline_value: Option<&str>,
+    args: &mut impl Iterator<Item = String>,
+) -> Result<usize, &'static str> {
+    let value = option_value(inline_value, args).ok_or("No buffer size has been provided")?;
+
+    match size::parse_size(&value).map(usize::try_from) {
+        Some(Ok(buffer_size)) if buffer_size > 0 => Ok(buffer_size),
+        _ => Err("The buffer size must be a positive number of bytes, optionally followed by K, M, G, KiB, MiB or GiB"),
+    }
+}
+
+pub fn run(config: Config) -> Result<ExitCode, Error> {
+    match &config.command {
+        Command::Hash => run_hash(&config).map(|_| ExitCode::SUCCESS),
+        Command::Manifest(command) => match command.run()? {
+            true => Ok(ExitCode::from(1)),
+            false => Ok(ExitCode::SUCCESS),
+        },
+        Command::Dupes(command) => command.run().map(|_| ExitCode::SUCCESS),
+        Command::Pieces(command) => match command.run()? {
+            true => Ok(ExitCode::from(1)),
+            false => Ok(ExitCode::SUCCESS),
+        },
+        // the demo fails if the forgery isn't accepted
+        Command::LengthExtension(command) => match command.run()? {
+            true => Ok(ExitCode::SUCCESS),
+            false => Ok(ExitCode::from(1)),
+        },
+        Command::Trace(command) => command.run().map(|_| ExitCode::SUCCESS),
+        Command::ExplainPadding(command) => command.run().map(|_| ExitCode::SUCCESS),
+        Command::Bench(command) => command.run().map(|_| ExitCode::SUCCESS),
+    }
+}
+
+fn run_hash(config: &Config) -> Result<(), Error> {
+    let hmac_key = match &config.hmac_key {
+        Some(source) => Some(source.load()?),
+        None => None,
+    };
+    let hmac_key = hmac_key.as_deref();
+
+    if config.check {
+        return check::check(&config.filenames, config.algorithm, hmac_key, config.jobs);
+    }
+
+    let cache = match &config.cache {
+        Some(file) => Some(HashCache::load(Path::new(file))?),
+        None => None,
+    };
+
+    let cache = match (config.verify_cache, cache) {
+        (Some(sample_size), Some(cache)) => return verify_cache(cache, sample_size),
+        (_, cache) => cache.map(Mutex::new),
+    };
+
+    let algorithm = if config.tree {
+        String::from("sha256-tree")
+    } else if hmac_key.is_some() {
+        format!("hmac-{}", config.algorithm.name())
+    } else {
+        config.algorithm.name().to_string()
+    };
+
+    let start = Instant::now();
+    let progress = config.progress.then(|| Progress::new(total_size(config)));
+    let progress = progress.as_ref();
+    let pipeline_stats = PipelineStats::default();
+
+    let hash_path = |filename: &String| {
+        let path = Path::new(filename);
+        let hasher = Hasher::new(config.algorithm, hmac_key);
+
+        // inputs which aren't streamed here count in the progress once done
+        let counted = |result: io::Result<(Digest, u64)>| {
+            if let (Some(progress), Ok((_, size))) = (progress, &result) {
+                progress.add(*size);
+            }
+            result
         };
 
-        Ok(Config { filename })
+        if config.tree {
+            counted(tree::tree_digest(path))
+        } else if filename == "-" {
+            // not locked, as the lock can't be sent to the reader thread of --pipeline
+            let mut stdin = io::stdin();
+            input::skip_to_range(&mut stdin, config.range)?;
+
+            let reader = ProgressReader::new(stdin, progress);
+            hash_stream(reader, config, hasher, &pipeline_stats)
+        } else if let Some(cache) = &cache {
+            counted(cache::hash_file_cached(path, &algorithm, hasher, cache))
+        } else if config.mmap {
+            counted(mmap::hash_mapped(path, config.range, hasher))
+        } else {
+            let reader = ProgressReader::new(input::open_range(path, config.range)?, progress);
+            hash_stream(reader, config, hasher, &pipeline_stats)
+        }
+    };
+
+    if config.watch {
+        return watch::watch(&config.filenames, hash_path, &mut io::stdout().lock());
+    }
+
+    let mut writer = RecordWriter::new(io::stdout().lock(), config.format);
+    let mut write_result = Ok(());
+    let mut nb_failed = 0;
+    let mut stats = Stats {
+        bytes: 0,
+        files: 0,
+        elapsed: Duration::ZERO,
+    };
+
+    thread::scope(|scope| {
+        if let Some(progress) = progress {
+            let stderr = io::stderr();
+            let is_terminal = stderr.is_terminal();
+            scope.spawn(move || progress.report(stderr, is_terminal));
+        }
+
+        pool::map_ordered(
+            &config.filenames,
+            config.jobs,
+            hash_path,
+            |filename, result| {
+                match &result {
+                    Ok((_, size)) => {
+                        stats.bytes += size;
+                        stats.files += 1;
+                    }
+                    Err(_) => nb_failed += 1,
+                }
+
+                let record = Record {
+                    path: filename,
+                    algorithm: &algorithm,
+                    result: result.map_err(|err| err.to_string()),
+                };
+
+                if write_result.is_ok() {
+                    write_result = writer.write(&record);
+                }
+            },
+        );
+
+        if let Some(progress) = progress {
+            progress.finish();
+        }
+    });
+
+    write_result?;
+    writer.finish()?;
+
+    if config.stats {
+        stats.elapsed = start.elapsed();
+        eprintln!("{stats}");
+
+        if config.pipeline {
+            eprintln!("{pipeline_stats}");
+        }
+    }
+
+    if let Some(cache) = cache {
+        // a panicking worker would have been re-raised by the pool, so the lock isn't poisoned
+        let mut cache = cache
+            .into_inner()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        cache
+            .save()
+            .map_err(|err| Error::io(cache.file().display(), err))?;
+    }
+
+    if nb_failed > 0 {
+        return Err(Error::Failed(format!(
+            "{nb_failed} input(s) could not be hashed"
+        )));
+    }
+
+    Ok(())
+}
+
+/// Hashes the range of `reader` through the read buffer, from a reader thread with `--pipeline`
+fn hash_stream(
+    reader: impl Read + Send,
+    config: &Config,
+    hasher: Hasher,
+    pipeline_stats: &PipelineStats,
+) -> io::Result<(Digest, u64)> {
+    input::hash_range(reader, config.range, |reader| match config.pipeline {
+        true => pipeline::hash_pipelined(reader, hasher, config.buffer_size, pipeline_stats),
+        false => input::hash_buffered(reader, hasher, config.buffer_size),
+    })
+}
+
+/// Sum of the input sizes, unknown if some can't be known upfront
+fn total_size(config: &Config) -> Option<u64> {
+    if config.tree {
+        return None;
     }
+
+    config
+        .filenames
+        .iter()
+        .map(|filename| match fs::metadata(filename) {
+            Ok(metadata) if metadata.is_file() => {
+                let after_offset = metadata.len().saturating_sub(config.range.offset);
+                Some(config.range.length.unwrap_or(after_offset))
+            }
+            // inputs which can't be read won't be hashed at all
+            Err(_) if filename != "-" => Some(0),
+            _ => None,
+        })
+        .sum()
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let content_bytes = fs::read(config.filename)?;
+fn verify_cache(mut cache: HashCache, sample_size: usize) -> Result<(), Error> {
+    let (nb_checked, stale) = cache.verify_sample(sample_size, |algorithm| {
+        Algorithm::from_name(algorithm).map(|algorithm| algorithm.hasher())
+    });
+
+    for path in &stale {
+        println!("{}: STALE", path.display());
+    }
+
+    cache
+        .save()
+        .map_err(|err| Error::io(cache.file().display(), err))?;
+
+    println!(
+        "{nb_checked} cache entries checked, {} stale entries evicted, {} entries left",
+        stale.len(),
+        cache.len()
+    );
 
-    let hash = sha_256_scratch::sha_256(content_bytes);
-    println!("{hash}");
+    if !stale.is_empty() {
+        return Err(Error::Failed(String::from("the cache had stale entries")));
+    }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Config, Error> {
+        let args = ["sha256sum_from_scratch"].iter().chain(args);
+
+        Config::new(args.map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn config_jobs() {
+        assert_eq!(parse(&["-j", "3", "a"]).unwrap().jobs, 3);
+        assert_eq!(parse(&["-j3", "a"]).unwrap().jobs, 3);
+        assert_eq!(parse(&["--jobs=3", "a"]).unwrap().jobs, 3);
+        assert_eq!(parse(&["a", "--jobs", "3"]).unwrap().jobs, 3);
+
+        assert!(parse(&["-j", "0", "a"]).is_err());
+        assert!(parse(&["a", "-j"]).is_err());
+    }
+
+    #[test]
+    fn config_paths_order() {
+        let config = parse(&["b", "a", "--", "-c"]).unwrap();
+        assert_eq!(config.filenames, vec!["b", "a", "-c"]);
+
+        let config = parse(&["--sort", "b", "a"]).unwrap();
+        assert_eq!(config.filenames, vec!["a", "b"]);
+    }
+
+    #[test]
+    fn config_algorithm() {
+        assert_eq!(parse(&["a"]).unwrap().algorithm, Algorithm::Sha256);
+        assert_eq!(
+            parse(&["-a", "sha224", "a"]).unwrap().algorithm,
+            Algorithm::Sha224
+        );
+        assert_eq!(
+            parse(&["-a", "sha256d", "a"]).unwrap().algorithm,
+            Algorithm::Sha256d
+        );
+
+        let binary = ["sha224sum_from_scratch", "a"].map(String::from);
+        assert_eq!(
+            Config::new(binary.into_iter()).unwrap().algorithm,
+            Algorithm::Sha224
+        );
+
+        assert!(parse(&["--algorithm=md5", "a"]).is_err());
+        assert!(parse(&["--tree", "-a", "sha224", "a"]).is_err());
+    }
+
+    #[test]
+    fn config_hmac_key() {
+        let config = parse(&["--hmac-key-env", "KEY", "a"]).unwrap();
+        assert_eq!(
+            config.hmac_key,
+            Some(HmacKeySource::Env(String::from("KEY")))
+        );
+
+        let config = parse(&["--hmac-key-file=key.bin", "a"]).unwrap();
+        assert_eq!(
+            config.hmac_key,
+            Some(HmacKeySource::File(String::from("key.bin")))
+        );
+
+        assert!(parse(&["--hmac-key", "secret", "a"]).is_err());
+        assert!(parse(&["--hmac-key-env", "A", "--hmac-key-file", "b", "a"]).is_err());
+        assert!(parse(&["--hmac-key-env", "A", "-a", "sha224", "a"]).is_err());
+    }
+
+    #[test]
+    fn config_format() {
+        assert_eq!(parse(&["a"]).unwrap().format, Format::Bare);
+        assert_eq!(parse(&["a", "b"]).unwrap().format, Format::Gnu);
+        assert_eq!(
+            parse(&["--format", "gnu", "a"]).unwrap().format,
+            Format::Gnu
+        );
+        assert_eq!(parse(&["--tag", "a"]).unwrap().format, Format::Bsd);
+        assert_eq!(
+            parse(&["--format", "ndjson", "a"]).unwrap().format,
+            Format::Ndjson
+        );
+
+        assert!(parse(&["--format=xml", "a"]).is_err());
+        assert!(parse(&["--check", "--format=json", "a"]).is_err());
+    }
+
+    #[test]
+    fn config_subcommand() {
+        let config = parse(&["manifest", "diff", "a", "b"]).unwrap();
+
+        let command_good = Command::Manifest(ManifestCommand::Diff {
+            old: String::from("a"),
+            new: String::from("b"),
+            json: false,
+        });
+        assert_eq!(config.command, command_good);
+
+        // a file named as a subcommand can still be hashed
+        assert_eq!(parse(&["./manifest"]).unwrap().command, Command::Hash);
+    }
+
+    #[test]
+    fn config_range() {
+        assert!(parse(&["a"]).unwrap().range.is_whole_input());
+
+        let config = parse(&["--offset", "1MiB", "--length=512", "a"]).unwrap();
+        let range_good = ByteRange {
+            offset: 1 << 20,
+            length: Some(512),
+        };
+        assert_eq!(config.range, range_good);
+
+        assert!(parse(&["--offset", "1.5G", "a"]).is_err());
+        assert!(parse(&["--length", "1", "--tree", "a"]).is_err());
+    }
+
+    #[test]
+    fn config_input_strategy() {
+        let config = parse(&["a"]).unwrap();
+        assert_eq!((config.buffer_size, config.mmap), (BUFFER_SIZE, false));
+
+        assert_eq!(
+            parse(&["--buffer-size", "1M", "a"]).unwrap().buffer_size,
+            1 << 20
+        );
+        assert!(parse(&["--buffer-size=0", "a"]).is_err());
+        assert!(parse(&["--buffer-size", "4K", "--check", "a"]).is_err());
+
+        if cfg!(feature = "mmap") {
+            assert!(parse(&["--mmap", "a"]).unwrap().mmap);
+            assert!(parse(&["--mmap", "-"]).is_err());
+            assert!(parse(&["--mmap", "--buffer-size", "4K", "a"]).is_err());
+        } else {
+            assert!(parse(&["--mmap", "a"]).is_err());
+        }
+
+        assert!(parse(&["--pipeline", "-"]).unwrap().pipeline);
+        assert!(parse(&["--pipeline", "--cache", "c", "a"]).is_err());
+    }
+
+    #[test]
+    fn config_progress_and_stats() {
+        let config = parse(&["--progress", "--stats", "a"]).unwrap();
+        assert!(config.progress && config.stats);
+
+        assert!(parse(&["--progress", "--watch", "a"]).is_err());
+        assert!(parse(&["--stats", "-c", "a"]).is_err());
+    }
+
+    #[test]
+    fn config_watch() {
+        assert!(parse(&["--watch", "a"]).unwrap().watch);
+        assert!(!parse(&["a"]).unwrap().watch);
+
+        assert!(parse(&["--watch", "-"]).is_err());
+        assert!(parse(&["--watch", "--format", "json", "a"]).is_err());
+        assert!(parse(&["--watch", "--check", "a"]).is_err());
+    }
+
+    #[test]
+    fn config_cache() {
+        let config = parse(&["--cache", "c", "a"]).unwrap();
+        assert_eq!(config.cache, Some(String::from("c")));
+        assert_eq!(config.verify_cache, None);
+
+        let config = parse(&["--cache", "c", "--verify-cache"]).unwrap();
+        assert_eq!(config.verify_cache, Some(DEFAULT_VERIFY_SAMPLE));
+
+        let config = parse(&["--cache", "c", "--verify-cache=5"]).unwrap();
+        assert_eq!(config.verify_cache, Some(5));
+
+        assert!(parse(&["--verify-cache"]).is_err());
+        assert!(parse(&["--cache", "c", "--verify-cache", "a"]).is_err());
+        assert!(parse(&["--cache", "c", "--tree", "a"]).is_err());
+    }
+
+    #[test]
+    fn config_invalid() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--unknown", "a"]).is_err());
+        assert!(parse(&["--tree", "--check", "a"]).is_err());
+    }
+}
@@ -1,15 +1,20 @@
 use sha256sum_from_scratch::Config;
 use std::env;
-use std::process;
+use std::process::{self, ExitCode};
 
-fn main() {
+fn main() -> ExitCode {
     let config = Config::new(env::args()).unwrap_or_else(|err| {
         eprintln!("Invalid argument: {err}");
         process::exit(1);
     });
 
-    if let Err(err) = sha256sum_from_scratch::run(config) {
-        eprintln!("error during execution:{err}");
-        process::exit(1);
+    let failure_code = config.command.failure_code();
+
+    match sha256sum_from_scratch::run(config) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("error during execution:{err}");
+            ExitCode::from(failure_code)
+        }
     }
 }
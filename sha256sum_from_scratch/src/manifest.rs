@@ -0,0 +1,551 @@
+//! `manifest` subcommand: snapshots of a directory and differences between two snapshots.
+//!
+//! A manifest lists every regular file below a directory, sorted by path. Symbolic links are
+//! not followed and, like other special files, are not listed. The format is line based:
+//!
+//! ~~~text
+//! # sha256sum_from_scratch manifest v1
+//! <sha256 hex digest> <size in bytes> <path relative to the directory>
+//! ~~~
+//!
+//! Paths use `/` as separator, and backslashes, newlines and carriage returns in them are escaped
+//! as `\\`, `\n` and `\r`: a raw carriage return at the end of a line would be dropped as part of a CRLF.
+//!
+//! When diffing two manifests, a file whose path disappeared while a new path appeared with the
+//! same digest is reported as renamed, rather than as removed and added.
+
+use crate::algorithm::Algorithm;
+use crate::error::Error;
+use crate::output::json_string;
+use crate::{input, option_value, parse_jobs, pool, split_option};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+const HEADER: &str = "# sha256sum_from_scratch manifest v1";
+
+#[derive(Debug, PartialEq)]
+pub enum ManifestCommand {
+    Create {
+        dir: String,
+        output: Option<String>,
+        jobs: usize,
+    },
+    Diff {
+        old: String,
+        new: String,
+        json: bool,
+    },
+}
+
+impl ManifestCommand {
+    /// Parses the arguments following `manifest`
+    pub fn parse_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<ManifestCommand, &'static str> {
+        let action = args
+            .next()
+            .ok_or("No manifest action has been provided (create or diff)")?;
+
+        let mut paths = Vec::new();
+        let mut output = None;
+        let mut jobs = pool::default_jobs();
+        let mut json = false;
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                paths.push(arg);
+                continue;
+            }
+
+            let (name, inline_value) = split_option(&arg);
+
+            match (action.as_str(), name) {
+                ("create", "-o" | "--output") => {
+                    output = Some(
+                        option_value(inline_value, &mut args)
+                            .ok_or("No output file has been provided")?,
+                    );
+                }
+                ("create", "-j" | "--jobs") => jobs = parse_jobs(inline_value, &mut args)?,
+                ("diff", "--json") => json = true,
+                _ => return Err("Unknown manifest option"),
+            }
+        }
+
+        match (action.as_str(), paths.as_slice()) {
+            ("create", [dir]) => Ok(ManifestCommand::Create {
+                dir: dir.clone(),
+                output,
+                jobs,
+            }),
+            ("create", _) => Err("manifest create expects one directory"),
+            ("diff", [old, new]) => Ok(ManifestCommand::Diff {
+                old: old.clone(),
+                new: new.clone(),
+                json,
+            }),
+            ("diff", _) => Err("manifest diff expects two manifests"),
+            _ => Err("Unknown manifest action (create or diff)"),
+        }
+    }
+
+    /// Runs the action, returns whether differences were found
+    pub fn run(&self) -> Result<bool, Error> {
+        match self {
+            ManifestCommand::Create { dir, output, jobs } => {
+                let manifest = Manifest::create(Path::new(dir), *jobs)?;
+
+                match output {
+                    Some(output) => {
+                        let write = || {
+                            let mut file = io::BufWriter::new(fs::File::create(output)?);
+                            manifest.write(&mut file)?;
+                            file.flush()
+                        };
+                        write().map_err(|err| Error::io(output, err))?;
+                    }
+                    None => manifest.write(&mut io::stdout().lock())?,
+                }
+
+                Ok(false)
+            }
+            ManifestCommand::Diff { old, new, json } => {
+                let old = Manifest::read(Path::new(old))?;
+                let new = Manifest::read(Path::new(new))?;
+
+                let diff = ManifestDiff::new(&old, &new);
+
+                let mut out = io::stdout().lock();
+                if *json {
+                    diff.write_json(&mut out)?;
+                } else {
+                    diff.write_human(&mut out)?;
+                }
+
+                Ok(!diff.is_empty())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ManifestEntry {
+    pub path: String,
+    pub size: u64,
+    pub digest: String, // lowercase hex
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>, // sorted by path
+}
+
+impl Manifest {
+    /// Hashes every regular file below `dir`, `jobs` files at a time
+    pub fn create(dir: &Path, jobs: usize) -> Result<Manifest, Error> {
+        let mut files = Vec::new();
+        collect_files(dir, "", &mut files).map_err(|err| Error::io(dir.display(), err))?;
+        files.sort();
+
+        let mut entries = Vec::new();
+        let mut first_error = None;
+
+        pool::map_ordered(
+            &files,
+            jobs,
+            |(_, path)| input::hash_file(path, Algorithm::Sha256.hasher()),
+            |(relative, path), result| match result {
+                Ok((digest, size)) => entries.push(ManifestEntry {
+                    path: relative.clone(),
+                    size,
+                    digest: digest.to_hex(),
+                }),
+                Err(err) => {
+                    first_error.get_or_insert_with(|| Error::io(path.display(), err));
+                }
+            },
+        );
+
+        if let Some(err) = first_error {
+            return Err(err);
+        }
+
+        Ok(Manifest { entries })
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "{HEADER}")?;
+
+        for entry in &self.entries {
+            writeln!(
+                out,
+                "{} {} {}",
+                entry.digest,
+                entry.size,
+                escape_path(&entry.path)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn read(path: &Path) -> Result<Manifest, Error> {
+        let content = fs::read_to_string(path).map_err(|err| Error::io(path.display(), err))?;
+
+        Manifest::parse(&content).map_err(|err| Error::malformed(path.display(), err))
+    }
+
+    pub fn parse(content: &str) -> Result<Manifest, String> {
+        let mut lines = content.lines().enumerate();
+
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(String::from("not a manifest, the header is missing"));
+        }
+
+        let mut entries = Vec::new();
+
+        for (index, line) in lines {
+            let invalid = || format!("line {} is invalid", index + 1);
+
+            let mut fields = line.splitn(3, ' ');
+            let (digest, size, path) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(digest), Some(size), Some(path)) => (digest, size, path),
+                _ => return Err(invalid()),
+            };
+
+            let is_valid_digest =
+                digest.len() == 64 && digest.chars().all(|c| c.is_ascii_hexdigit());
+
+            if !is_valid_digest {
+                return Err(invalid());
+            }
+
+            entries.push(ManifestEntry {
+                path: unescape_path(path).ok_or_else(invalid)?,
+                size: size.parse().map_err(|_| invalid())?,
+                digest: digest.to_ascii_lowercase(),
+            });
+        }
+
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(Manifest { entries })
+    }
+}
+
+fn collect_files(dir: &Path, prefix: &str, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+
+        let name = dir_entry.file_name().into_string().map_err(|name| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("path is not valid UTF-8: {}", name.to_string_lossy()),
+            )
+        })?;
+
+        let relative = if prefix.is_empty() {
+            name
+        } else {
+            format!("{prefix}/{name}")
+        };
+
+        let file_type = dir_entry.file_type()?;
+
+        if file_type.is_dir() {
+            collect_files(&path, &relative, files)?;
+        } else if file_type.is_file() {
+            files.push((relative, path));
+        }
+    }
+
+    Ok(())
+}
+
+pub(crate) fn escape_path(path: &str) -> String {
+    path.replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+pub(crate) fn unescape_path(path: &str) -> Option<String> {
+    let mut unescaped = String::new();
+    let mut chars = path.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+
+        match chars.next()? {
+            '\\' => unescaped.push('\\'),
+            'n' => unescaped.push('\n'),
+            'r' => unescaped.push('\r'),
+            _ => return None,
+        }
+    }
+
+    Some(unescaped)
+}
+
+/// Differences between an old and a new manifest, each list sorted by path
+#[derive(Debug, Default, PartialEq)]
+pub struct ManifestDiff {
+    pub added: Vec<ManifestEntry>,
+    pub removed: Vec<ManifestEntry>,
+    pub modified: Vec<(ManifestEntry, ManifestEntry)>, // (old, new)
+    pub renamed: Vec<(ManifestEntry, ManifestEntry)>,  // (old, new)
+}
+
+impl ManifestDiff {
+    pub fn new(old: &Manifest, new: &Manifest) -> ManifestDiff {
+        let old_entries: BTreeMap<&str, &ManifestEntry> = old
+            .entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry))
+            .collect();
+        let new_entries: BTreeMap<&str, &ManifestEntry> = new
+            .entries
+            .iter()
+            .map(|entry| (entry.path.as_str(), entry))
+            .collect();
+
+        let mut diff = ManifestDiff::default();
+
+        // added files, grouped by digest so that removed files can be matched against them
+        let mut added_by_digest: HashMap<&str, Vec<&ManifestEntry>> = HashMap::new();
+
+        for (path, entry) in new_entries.iter().rev() {
+            if !old_entries.contains_key(path) {
+                added_by_digest
+                    .entry(entry.digest.as_str())
+                    .or_default()
+                    .push(entry);
+            }
+        }
+
+        for (path, old_entry) in &old_entries {
+            match new_entries.get(path) {
+                Some(new_entry) if new_entry.digest != old_entry.digest => {
+                    diff.modified
+                        .push(((*old_entry).clone(), (*new_entry).clone()));
+                }
+                Some(_) => {}
+                None => {
+                    // paths were pushed in reverse order, so pop() pairs them in sorted order
+                    let renamed_to = added_by_digest
+                        .get_mut(old_entry.digest.as_str())
+                        .and_then(|candidates| candidates.pop());
+
+                    match renamed_to {
+                        Some(new_entry) => {
+                            diff.renamed.push(((*old_entry).clone(), new_entry.clone()))
+                        }
+                        None => diff.removed.push((*old_entry).clone()),
+                    }
+                }
+            }
+        }
+
+        diff.added = added_by_digest.into_values().flatten().cloned().collect();
+        diff.added.sort_by(|a, b| a.path.cmp(&b.path));
+
+        diff
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.modified.is_empty()
+            && self.renamed.is_empty()
+    }
+
+    pub fn write_human(&self, out: &mut impl Write) -> io::Result<()> {
+        for entry in &self.added {
+            writeln!(out, "added:    {}", escape_path(&entry.path))?;
+        }
+        for entry in &self.removed {
+            writeln!(out, "removed:  {}", escape_path(&entry.path))?;
+        }
+        for (old, _) in &self.modified {
+            writeln!(out, "modified: {}", escape_path(&old.path))?;
+        }
+        for (old, new) in &self.renamed {
+            writeln!(
+                out,
+                "renamed:  {} -> {}",
+                escape_path(&old.path),
+                escape_path(&new.path)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    pub fn write_json(&self, out: &mut impl Write) -> io::Result<()> {
+        let entry_json = |entry: &ManifestEntry| {
+            format!(
+                "{{\"path\":{},\"size\":{},\"sha256\":{}}}",
+                json_string(&entry.path),
+                entry.size,
+                json_string(&entry.digest)
+            )
+        };
+        let pair_json = |(old, new): &(ManifestEntry, ManifestEntry)| {
+            format!(
+                "{{\"old\":{},\"new\":{}}}",
+                entry_json(old),
+                entry_json(new)
+            )
+        };
+
+        let added: Vec<String> = self.added.iter().map(entry_json).collect();
+        let removed: Vec<String> = self.removed.iter().map(entry_json).collect();
+        let modified: Vec<String> = self.modified.iter().map(pair_json).collect();
+        let renamed: Vec<String> = self.renamed.iter().map(pair_json).collect();
+
+        writeln!(
+            out,
+            "{{\"added\":[{}],\"removed\":[{}],\"modified\":[{}],\"renamed\":[{}]}}",
+            added.join(","),
+            removed.join(","),
+            modified.join(","),
+            renamed.join(",")
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::new_test_dir;
+
+    ////////////////// functions for setting up unit tests scenarios
+    fn entry(path: &str, size: u64, digest_char: char) -> ManifestEntry {
+        ManifestEntry {
+            path: String::from(path),
+            size,
+            digest: digest_char.to_string().repeat(64),
+        }
+    }
+
+    ////////////////// unit tests
+    #[test]
+    fn create_and_parse_manifest() {
+        let dir = new_test_dir("manifest_create");
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("hi"), "hi").unwrap();
+        fs::write(dir.join("back\\slash\nnewline"), "").unwrap();
+
+        let manifest = Manifest::create(&dir, 2).unwrap();
+
+        let mut written = Vec::new();
+        manifest.write(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+
+        let written_good = format!(
+            "{HEADER}\n{} 0 back\\\\slash\\nnewline\n{} 2 sub/hi\n",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+            "8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4"
+        );
+
+        assert_eq!(written, written_good);
+        assert_eq!(Manifest::parse(&written).unwrap(), manifest);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn carriage_return_in_path() {
+        let dir = new_test_dir("manifest_carriage_return");
+        fs::write(dir.join("ends with\r"), "").unwrap();
+
+        let manifest = Manifest::create(&dir, 1).unwrap();
+        assert_eq!(manifest.entries[0].path, "ends with\r");
+
+        let mut written = Vec::new();
+        manifest.write(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+
+        assert!(written.ends_with(" ends with\\r\n"));
+        assert_eq!(Manifest::parse(&written).unwrap(), manifest);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn parse_invalid_manifest() {
+        assert!(Manifest::parse("abc 1 file").is_err());
+        assert!(Manifest::parse(&format!("{HEADER}\nabc 1 file")).is_err());
+        assert!(Manifest::parse(&format!("{HEADER}\n{} x file", "a".repeat(64))).is_err());
+    }
+
+    #[test]
+    fn diff_manifests() {
+        let old = Manifest {
+            entries: vec![
+                entry("kept", 1, 'a'),
+                entry("modified", 1, 'b'),
+                entry("removed", 1, 'c'),
+                entry("renamed_old", 1, 'd'),
+            ],
+        };
+        let new = Manifest {
+            entries: vec![
+                entry("added", 1, 'e'),
+                entry("kept", 1, 'a'),
+                entry("modified", 2, 'f'),
+                entry("renamed_new", 1, 'd'),
+            ],
+        };
+
+        let diff = ManifestDiff::new(&old, &new);
+
+        let diff_good = ManifestDiff {
+            added: vec![entry("added", 1, 'e')],
+            removed: vec![entry("removed", 1, 'c')],
+            modified: vec![(entry("modified", 1, 'b'), entry("modified", 2, 'f'))],
+            renamed: vec![(entry("renamed_old", 1, 'd'), entry("renamed_new", 1, 'd'))],
+        };
+
+        assert_eq!(diff, diff_good);
+        assert!(!diff.is_empty());
+        assert!(ManifestDiff::new(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn diff_renames_pair_one_to_one() {
+        let old = Manifest {
+            entries: vec![entry("a", 1, 'a'), entry("b", 1, 'a')],
+        };
+        let new = Manifest {
+            entries: vec![entry("c", 1, 'a')],
+        };
+
+        let diff = ManifestDiff::new(&old, &new);
+
+        assert_eq!(diff.renamed, vec![(entry("a", 1, 'a'), entry("c", 1, 'a'))]);
+        assert_eq!(diff.removed, vec![entry("b", 1, 'a')]);
+        assert!(diff.added.is_empty());
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let args = ["create", "dir", "-o", "out", "-j2"].map(String::from);
+        let command = ManifestCommand::parse_args(args.into_iter()).unwrap();
+
+        let command_good = ManifestCommand::Create {
+            dir: String::from("dir"),
+            output: Some(String::from("out")),
+            jobs: 2,
+        };
+        assert_eq!(command, command_good);
+
+        let args = ["diff", "a", "--json"].map(String::from);
+        assert!(ManifestCommand::parse_args(args.into_iter()).is_err());
+    }
+}
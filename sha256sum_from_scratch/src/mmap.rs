@@ -0,0 +1,97 @@
+//! `--mmap`: files are hashed straight from a read-only memory mapping instead of being read
+//! through a buffer, which saves copying them into user space.
+//!
+//! Mapping needs the `memmap2` crate, so this is only available with the `mmap` feature.
+//! A mapped file must not be truncated or written while it is hashed: reading pages past
+//! the new end kills the process with SIGBUS, and the digest of a file being written is meaningless anyway.
+
+use crate::algorithm::Hasher;
+use crate::input::ByteRange;
+use sha_256_scratch::Digest;
+use std::io;
+use std::path::Path;
+
+/// Returns the digest of the range of the file and its size in bytes, read from a mapping
+#[cfg(feature = "mmap")]
+pub fn hash_mapped(path: &Path, range: ByteRange, mut hasher: Hasher) -> io::Result<(Digest, u64)> {
+    let file = std::fs::File::open(path)?;
+    let metadata = file.metadata()?;
+
+    if !metadata.is_file() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "only regular files can be memory-mapped",
+        ));
+    }
+
+    let length = crate::input::range_len(range, metadata.len())?;
+
+    // empty mappings are rejected by some systems
+    if length == 0 {
+        return Ok((hasher.finalize(), 0));
+    }
+
+    let map_len = usize::try_from(length).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the range doesn't fit in the address space",
+        )
+    })?;
+
+    // SAFETY: the mapping is read-only and private to this function; it is only unsound if the
+    // file is changed by another process while being hashed, which --mmap documents as forbidden
+    let map = unsafe {
+        memmap2::MmapOptions::new()
+            .offset(range.offset)
+            .len(map_len)
+            .map(&file)?
+    };
+
+    hasher.update(&map);
+
+    Ok((hasher.finalize(), length))
+}
+
+#[cfg(not(feature = "mmap"))]
+pub fn hash_mapped(_path: &Path, _range: ByteRange, _hasher: Hasher) -> io::Result<(Digest, u64)> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "--mmap needs the mmap feature",
+    ))
+}
+
+#[cfg(all(test, feature = "mmap"))]
+mod tests {
+    use super::*;
+    use crate::algorithm::Algorithm;
+    use crate::input;
+    use crate::temp::temp_path;
+    use std::fs;
+
+    #[test]
+    fn same_digest_as_streaming() {
+        let file = temp_path("mmap");
+        let content: Vec<u8> = (0..10_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        fs::write(&file, &content).unwrap();
+
+        let mapped = |offset, length| {
+            hash_mapped(
+                &file,
+                ByteRange { offset, length },
+                Algorithm::Sha256.hasher(),
+            )
+        };
+        let streamed =
+            |bytes: &[u8]| input::hash_reader(bytes, Algorithm::Sha256.hasher()).unwrap();
+
+        assert_eq!(mapped(0, None).unwrap(), streamed(&content));
+        assert_eq!(
+            mapped(4097, Some(100)).unwrap(),
+            streamed(&content[4097..4197])
+        );
+        assert_eq!(mapped(10_000, None).unwrap(), streamed(b""));
+        assert!(mapped(9_999, Some(2)).is_err());
+
+        fs::remove_file(file).unwrap();
+    }
+}
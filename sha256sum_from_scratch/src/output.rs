@@ -0,0 +1,323 @@
+//! Output formats of the hashing mode.
+//!
+//! Besides the bare digest (the default for a single input), GNU (`<hex>  <path>`, the default
+//! for several inputs) and BSD-tagged (`<TAG> (<path>) = <hex>`) lines, records can be
+//! written for machine consumption as a JSON array, NDJSON (one JSON object per line) or CSV.
+//! Each record has the fields `path`, `size` (bytes hashed), `algorithm`, `hex`, `base64` and `error`:
+//! `size`, `hex` and `base64` are null (empty in CSV) for inputs which failed, `error` is null otherwise.
+
+use sha_256_scratch::Digest;
+use std::io::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Bare,
+    Gnu,
+    Bsd,
+    Json,
+    Ndjson,
+    Csv,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Option<Format> {
+        match name {
+            "gnu" => Some(Format::Gnu),
+            "bsd" => Some(Format::Bsd),
+            "json" => Some(Format::Json),
+            "ndjson" => Some(Format::Ndjson),
+            "csv" => Some(Format::Csv),
+            _ => None,
+        }
+    }
+}
+
+/// Result of hashing one input
+pub struct Record<'a> {
+    pub path: &'a str,
+    pub algorithm: &'a str,
+    pub result: Result<(Digest, u64), String>, // digest and size in bytes, or error message
+}
+
+/// Writes records in the given format, taking care of the header and separators
+pub struct RecordWriter<W: Write> {
+    out: W,
+    format: Format,
+    nb_written: usize,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(out: W, format: Format) -> RecordWriter<W> {
+        Self {
+            out,
+            format,
+            nb_written: 0,
+        }
+    }
+
+    pub fn write(&mut self, record: &Record) -> io::Result<()> {
+        match self.format {
+            Format::Bare | Format::Gnu | Format::Bsd => self.write_line(record)?,
+            Format::Json => {
+                let separator = if self.nb_written == 0 { "[\n" } else { ",\n" };
+                write!(self.out, "{separator}  {}", json_object(record))?;
+            }
+            Format::Ndjson => writeln!(self.out, "{}", json_object(record))?,
+            Format::Csv => {
+                if self.nb_written == 0 {
+                    writeln!(self.out, "path,size,algorithm,hex,base64,error")?;
+                }
+
+                writeln!(self.out, "{}", csv_row(record))?;
+            }
+        }
+
+        self.nb_written += 1;
+
+        Ok(())
+    }
+
+    /// Writes what is expected after the last record
+    pub fn finish(&mut self) -> io::Result<()> {
+        match self.format {
+            Format::Json if self.nb_written == 0 => writeln!(self.out, "[]")?,
+            Format::Json => writeln!(self.out, "\n]")?,
+            Format::Csv if self.nb_written == 0 => {
+                writeln!(self.out, "path,size,algorithm,hex,base64,error")?
+            }
+            _ => {}
+        }
+
+        self.out.flush()
+    }
+
+    fn write_line(&mut self, record: &Record) -> io::Result<()> {
+        match &record.result {
+            Ok((digest, _)) if self.format == Format::Bare => writeln!(self.out, "{digest}"),
+            Ok((digest, _)) if self.format == Format::Bsd => {
+                let tag = record.algorithm.to_ascii_uppercase();
+                writeln!(self.out, "{tag} ({}) = {digest}", record.path)
+            }
+            Ok((digest, _)) => writeln!(self.out, "{digest}  {}", record.path),
+            // errors are reported on stderr in line formats
+            Err(err) => {
+                eprintln!("{}: {err}", record.path);
+                Ok(())
+            }
+        }
+    }
+}
+
+fn json_object(record: &Record) -> String {
+    let (size, hex, base64, error) = match &record.result {
+        Ok((digest, size)) => (
+            size.to_string(),
+            json_string(&digest.to_hex()),
+            json_string(&base64(digest.as_bytes())),
+            String::from("null"),
+        ),
+        Err(err) => (
+            String::from("null"),
+            String::from("null"),
+            String::from("null"),
+            json_string(err),
+        ),
+    };
+
+    format!(
+        "{{\"path\":{},\"size\":{size},\"algorithm\":{},\"hex\":{hex},\"base64\":{base64},\"error\":{error}}}",
+        json_string(record.path),
+        json_string(record.algorithm)
+    )
+}
+
+/// Lowercase hexadecimal representation of any bytes, digests having their own `to_hex`
+pub(crate) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+pub(crate) fn json_string(value: &str) -> String {
+    let mut escaped = String::from("\"");
+
+    for c in value.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped.push('"');
+    escaped
+}
+
+fn csv_row(record: &Record) -> String {
+    let fields = match &record.result {
+        Ok((digest, size)) => [
+            record.path.to_string(),
+            size.to_string(),
+            record.algorithm.to_string(),
+            digest.to_hex(),
+            base64(digest.as_bytes()),
+            String::new(),
+        ],
+        Err(err) => [
+            record.path.to_string(),
+            String::new(),
+            record.algorithm.to_string(),
+            String::new(),
+            String::new(),
+            err.clone(),
+        ],
+    };
+
+    fields.map(|field| csv_field(&field)).join(",")
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Standard base64 encoding (RFC 4648), with padding
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::new();
+
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | (*byte as u32) << (16 - 8 * i)
+        });
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha_256_scratch::Sha256;
+
+    fn hi_digest() -> Digest {
+        let mut hasher = Sha256::new();
+        hasher.update(b"hi");
+
+        hasher.finalize()
+    }
+
+    fn write_records(format: Format, records: &[Record]) -> String {
+        let mut out = Vec::new();
+        let mut writer = RecordWriter::new(&mut out, format);
+
+        for record in records {
+            writer.write(record).unwrap();
+        }
+        writer.finish().unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn base64_test() {
+        // test vectors from RFC 4648
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+        assert_eq!(base64(b"fooba"), "Zm9vYmE=");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn line_records() {
+        let records = [Record {
+            path: "hi",
+            algorithm: "sha256",
+            result: Ok((hi_digest(), 2)),
+        }];
+        let hex = "8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4";
+
+        assert_eq!(write_records(Format::Bare, &records), format!("{hex}\n"));
+        assert_eq!(write_records(Format::Gnu, &records), format!("{hex}  hi\n"));
+        assert_eq!(
+            write_records(Format::Bsd, &records),
+            format!("SHA256 (hi) = {hex}\n")
+        );
+    }
+
+    #[test]
+    fn ndjson_records() {
+        let records = [
+            Record {
+                path: "dir/\"hi\"",
+                algorithm: "sha256",
+                result: Ok((hi_digest(), 2)),
+            },
+            Record {
+                path: "missing",
+                algorithm: "sha256",
+                result: Err(String::from("No such file or directory")),
+            },
+        ];
+
+        let output_good = concat!(
+            "{\"path\":\"dir/\\\"hi\\\"\",\"size\":2,\"algorithm\":\"sha256\",",
+            "\"hex\":\"8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4\",",
+            "\"base64\":\"j0NDRmSPa5bfid2pAcUXaxCm2Dlh3TwayItZstwyeqQ=\",\"error\":null}\n",
+            "{\"path\":\"missing\",\"size\":null,\"algorithm\":\"sha256\",\"hex\":null,",
+            "\"base64\":null,\"error\":\"No such file or directory\"}\n",
+        );
+
+        assert_eq!(write_records(Format::Ndjson, &records), output_good);
+    }
+
+    #[test]
+    fn json_array() {
+        assert_eq!(write_records(Format::Json, &[]), "[]\n");
+
+        let record = Record {
+            path: "a",
+            algorithm: "sha256",
+            result: Err(String::from("error")),
+        };
+        let output = write_records(Format::Json, &[record]);
+
+        assert!(output.starts_with("[\n  {\"path\":\"a\""));
+        assert!(output.ends_with("}\n]\n"));
+    }
+
+    #[test]
+    fn csv_rows() {
+        let record = Record {
+            path: "a,b",
+            algorithm: "sha256",
+            result: Ok((hi_digest(), 2)),
+        };
+
+        let output_good = concat!(
+            "path,size,algorithm,hex,base64,error\n",
+            "\"a,b\",2,sha256,8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4,",
+            "j0NDRmSPa5bfid2pAcUXaxCm2Dlh3TwayItZstwyeqQ=,\n",
+        );
+
+        assert_eq!(write_records(Format::Csv, &[record]), output_good);
+    }
+}
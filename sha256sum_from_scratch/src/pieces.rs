@@ -0,0 +1,400 @@
+//! `pieces` subcommand: a SHA-256 for every fixed-size piece of a file, for resumable transfers
+//! and partial verification.
+//!
+//! `pieces --size <SIZE> <FILE>` lists the pieces in a line based format:
+//!
+//! ~~~text
+//! # sha256sum_from_scratch pieces v1
+//! size <piece size in bytes>
+//! <offset> <length> <sha256 hex digest>
+//! ...
+//! total <file size in bytes> <sha256 hex digest of the whole file>
+//! ~~~
+//!
+//! `pieces --verify <LIST> <FILE>` hashes the file again with the piece size of the list,
+//! and reports each piece which is corrupt, or missing since the file got shorter.
+
+use crate::error::Error;
+use crate::input::BUFFER_SIZE;
+use crate::{option_value, size, split_option};
+use sha_256_scratch::{Digest, PieceHasher, Pieces};
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+const HEADER: &str = "# sha256sum_from_scratch pieces v1";
+
+#[derive(Debug, PartialEq)]
+pub enum PiecesCommand {
+    List { file: String, piece_size: usize },
+    Verify { list: String, file: String },
+}
+
+impl PiecesCommand {
+    /// Parses the arguments following `pieces`
+    pub fn parse_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<PiecesCommand, &'static str> {
+        let mut paths = Vec::new();
+        let mut piece_size = None;
+        let mut list = None;
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with('-') {
+                paths.push(arg);
+                continue;
+            }
+
+            let (name, inline_value) = split_option(&arg);
+
+            match name {
+                "-s" | "--size" => {
+                    let value = option_value(inline_value, &mut args)
+                        .ok_or("No piece size has been provided")?;
+
+                    piece_size = match size::parse_size(&value).map(usize::try_from) {
+                        Some(Ok(piece_size)) if piece_size > 0 => Some(piece_size),
+                        _ => return Err("The piece size must be a positive number of bytes, optionally followed by K, M, G, KiB, MiB or GiB"),
+                    };
+                }
+                "--verify" => {
+                    list = Some(
+                        option_value(inline_value, &mut args)
+                            .ok_or("No piece list has been provided")?,
+                    );
+                }
+                _ => return Err("Unknown pieces option"),
+            }
+        }
+
+        let [file] = <[String; 1]>::try_from(paths).map_err(|_| "pieces expects one file")?;
+
+        match (piece_size, list) {
+            (Some(piece_size), None) => Ok(PiecesCommand::List { file, piece_size }),
+            (None, Some(list)) => Ok(PiecesCommand::Verify { list, file }),
+            (Some(_), Some(_)) => {
+                Err("--verify reads the piece size from the list, --size can't be given")
+            }
+            (None, None) => Err("pieces expects --size or --verify"),
+        }
+    }
+
+    /// Runs the command, returns whether corrupt pieces were found
+    pub fn run(&self) -> Result<bool, Error> {
+        match self {
+            PiecesCommand::List { file, piece_size } => {
+                let list = PieceList::hash_file(Path::new(file), *piece_size)?;
+                list.write(&mut io::stdout().lock())?;
+
+                Ok(false)
+            }
+            PiecesCommand::Verify { list, file } => {
+                let expected = PieceList::read(Path::new(list))?;
+                let actual = PieceList::hash_file(Path::new(file), expected.piece_size)?;
+
+                let mut out = io::stdout().lock();
+                let nb_bad = write_bad_pieces(&expected, &actual, &mut out)?;
+
+                writeln!(
+                    out,
+                    "{nb_bad} of {} pieces corrupt or missing",
+                    expected.pieces.len()
+                )?;
+
+                Ok(nb_bad > 0 || actual.size > expected.size)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Piece {
+    pub offset: u64,
+    pub length: u64,
+    pub digest: Digest,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct PieceList {
+    pub piece_size: usize,
+    pub pieces: Vec<Piece>,
+    pub size: u64,
+    pub whole: Digest,
+}
+
+impl PieceList {
+    /// Reads the file once to get both its piece hashes and its whole digest
+    pub fn hash_file(path: &Path, piece_size: usize) -> Result<PieceList, Error> {
+        let io_error = |err| Error::io(path.display(), err);
+
+        let mut file = File::open(path).map_err(io_error)?;
+        let mut hasher = PieceHasher::new(piece_size)?;
+        let mut buffer = vec![0u8; BUFFER_SIZE];
+        let mut size = 0;
+
+        loop {
+            let read = match file.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read) => read,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(io_error(err)),
+            };
+
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+        }
+
+        Ok(PieceList::new(hasher.finalize(), size))
+    }
+
+    pub fn new(pieces: Pieces, size: u64) -> PieceList {
+        let piece_size = pieces.piece_size as u64;
+        let whole = pieces.whole;
+
+        let pieces = pieces
+            .pieces
+            .into_iter()
+            .enumerate()
+            .map(|(index, digest)| {
+                let offset = index as u64 * piece_size;
+
+                Piece {
+                    offset,
+                    length: piece_size.min(size - offset),
+                    digest,
+                }
+            })
+            .collect();
+
+        PieceList {
+            piece_size: piece_size as usize,
+            pieces,
+            size,
+            whole,
+        }
+    }
+
+    pub fn write(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "{HEADER}")?;
+        writeln!(out, "size {}", self.piece_size)?;
+
+        for piece in &self.pieces {
+            writeln!(out, "{} {} {}", piece.offset, piece.length, piece.digest)?;
+        }
+
+        writeln!(out, "total {} {}", self.size, self.whole)
+    }
+
+    pub fn read(path: &Path) -> Result<PieceList, Error> {
+        let content = fs::read_to_string(path).map_err(|err| Error::io(path.display(), err))?;
+
+        PieceList::parse(&content).map_err(|err| Error::malformed(path.display(), err))
+    }
+
+    pub fn parse(content: &str) -> Result<PieceList, String> {
+        let mut lines = content.lines().enumerate();
+
+        if lines.next().map(|(_, line)| line) != Some(HEADER) {
+            return Err(String::from("not a piece list, the header is missing"));
+        }
+
+        let mut piece_size = None;
+        let mut pieces = Vec::new();
+        let mut total = None;
+
+        for (index, line) in lines {
+            let invalid = || format!("line {} is invalid", index + 1);
+
+            let fields: Vec<&str> = line.split(' ').collect();
+
+            match fields.as_slice() {
+                ["size", value] if piece_size.is_none() => {
+                    piece_size = Some(
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|size| *size > 0)
+                            .ok_or_else(invalid)?,
+                    );
+                }
+                ["total", size, digest] if total.is_none() => {
+                    let size = size.parse().map_err(|_| invalid())?;
+                    total = Some((size, parse_digest(digest).ok_or_else(invalid)?));
+                }
+                [offset, length, digest] if total.is_none() => pieces.push(Piece {
+                    offset: offset.parse().map_err(|_| invalid())?,
+                    length: length.parse().map_err(|_| invalid())?,
+                    digest: parse_digest(digest).ok_or_else(invalid)?,
+                }),
+                _ => return Err(invalid()),
+            }
+        }
+
+        match (piece_size, total) {
+            (Some(piece_size), Some((size, whole))) => Ok(PieceList {
+                piece_size,
+                pieces,
+                size,
+                whole,
+            }),
+            _ => Err(String::from("the size or total line is missing")),
+        }
+    }
+}
+
+fn parse_digest(hex: &str) -> Option<Digest> {
+    Digest::from_hex(hex).filter(|digest| digest.as_bytes().len() == 32)
+}
+
+/// Writes a line for each expected piece which doesn't match, returns how many
+fn write_bad_pieces(
+    expected: &PieceList,
+    actual: &PieceList,
+    out: &mut impl Write,
+) -> io::Result<usize> {
+    let mut nb_bad = 0;
+
+    for (index, piece) in expected.pieces.iter().enumerate() {
+        let status = match actual.pieces.get(index) {
+            Some(actual_piece) if actual_piece == piece => continue,
+            Some(_) => "CORRUPT",
+            None => "MISSING",
+        };
+
+        writeln!(
+            out,
+            "piece {index} at offset {} ({} bytes): {status}",
+            piece.offset, piece.length
+        )?;
+        nb_bad += 1;
+    }
+
+    if actual.size > expected.size {
+        writeln!(
+            out,
+            "the file has {} more bytes than listed",
+            actual.size - expected.size
+        )?;
+    }
+
+    Ok(nb_bad)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::temp::temp_path;
+    use sha_256_scratch::piece_hashes;
+
+    ////////////////// functions for setting up unit tests scenarios
+    fn get_content() -> Vec<u8> {
+        (0..250u8).collect()
+    }
+
+    fn get_list(content: &[u8]) -> PieceList {
+        PieceList::new(piece_hashes(content, 100).unwrap(), content.len() as u64)
+    }
+
+    fn bad_pieces(expected: &PieceList, actual: &PieceList) -> (usize, String) {
+        let mut out = Vec::new();
+        let nb_bad = write_bad_pieces(expected, actual, &mut out).unwrap();
+
+        (nb_bad, String::from_utf8(out).unwrap())
+    }
+
+    ////////////////// unit tests
+    #[test]
+    fn write_and_parse_list() {
+        let list = get_list(&get_content());
+
+        let mut written = Vec::new();
+        list.write(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+
+        let lines: Vec<&str> = written.lines().collect();
+        assert_eq!(lines.len(), 6);
+        assert_eq!(lines[1], "size 100");
+        assert!(lines[4].starts_with("200 50 "));
+        assert!(lines[5].starts_with("total 250 "));
+
+        assert_eq!(PieceList::parse(&written).unwrap(), list);
+    }
+
+    #[test]
+    fn parse_invalid_list() {
+        assert!(PieceList::parse("size 100").is_err());
+        assert!(PieceList::parse(&format!("{HEADER}\nsize 0")).is_err());
+        assert!(PieceList::parse(&format!("{HEADER}\nsize 100\n0 100 abc")).is_err());
+        // the total line is missing
+        assert!(PieceList::parse(&format!("{HEADER}\nsize 100")).is_err());
+    }
+
+    #[test]
+    fn hash_file_pieces() {
+        let file = temp_path("pieces");
+        fs::write(&file, get_content()).unwrap();
+
+        let list = PieceList::hash_file(&file, 100).unwrap();
+        assert_eq!(list, get_list(&get_content()));
+
+        fs::remove_file(file).unwrap();
+    }
+
+    #[test]
+    fn verify_reports_bad_pieces() {
+        let content = get_content();
+        let expected = get_list(&content);
+
+        assert_eq!(bad_pieces(&expected, &expected), (0, String::new()));
+
+        let mut corrupt = content.clone();
+        corrupt[150] ^= 1;
+        assert_eq!(
+            bad_pieces(&expected, &get_list(&corrupt)),
+            (
+                1,
+                String::from("piece 1 at offset 100 (100 bytes): CORRUPT\n")
+            )
+        );
+
+        let (nb_bad, report) = bad_pieces(&expected, &get_list(&content[..120]));
+        assert_eq!(nb_bad, 2);
+        assert!(report.ends_with("piece 2 at offset 200 (50 bytes): MISSING\n"));
+
+        let mut longer = content.clone();
+        longer.push(0);
+        let (nb_bad, report) = bad_pieces(&expected, &get_list(&longer));
+        assert_eq!(nb_bad, 1);
+        assert!(report.ends_with("the file has 1 more bytes than listed\n"));
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let args = ["--size", "4MiB", "file"].map(String::from);
+        let command_good = PiecesCommand::List {
+            file: String::from("file"),
+            piece_size: 4 << 20,
+        };
+        assert_eq!(
+            PiecesCommand::parse_args(args.into_iter()),
+            Ok(command_good)
+        );
+
+        let args = ["--verify", "list", "file"].map(String::from);
+        let command_good = PiecesCommand::Verify {
+            list: String::from("list"),
+            file: String::from("file"),
+        };
+        assert_eq!(
+            PiecesCommand::parse_args(args.into_iter()),
+            Ok(command_good)
+        );
+
+        let parse =
+            |args: &[&str]| PiecesCommand::parse_args(args.iter().map(|arg| arg.to_string()));
+        assert!(parse(&["file"]).is_err());
+        assert!(parse(&["--size", "0", "file"]).is_err());
+        assert!(parse(&["--size", "1K", "a", "b"]).is_err());
+        assert!(parse(&["--size", "1K", "--verify", "list", "file"]).is_err());
+    }
+}
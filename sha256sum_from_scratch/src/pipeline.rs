@@ -0,0 +1,213 @@
+//! `--pipeline`: a reader thread fills one buffer while the hasher consumes the other, so waiting
+//! on slow storage (network filesystems, spinning disks) overlaps with hashing.
+//!
+//! Two buffers go back and forth between the threads through bounded channels: filled buffers to
+//! the hasher, emptied buffers back to the reader. The bytes reach the hasher in the same order as
+//! with the single-threaded path, so the digest is the same.
+//!
+//! With `--stats`, the time each side spent waiting for the other is reported: a reader waiting
+//! for free buffers means hashing is the bottleneck, a hasher waiting for data means I/O is.
+
+use crate::algorithm::Hasher;
+use sha_256_scratch::Digest;
+use std::fmt;
+use std::io::{self, Read};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const NB_BUFFERS: usize = 2;
+
+/// Time the reader and the hasher spent waiting for each other, summed over every input
+#[derive(Debug, Default)]
+pub struct PipelineStats {
+    reader_wait: AtomicU64, // in nanoseconds
+    hasher_wait: AtomicU64,
+}
+
+impl PipelineStats {
+    pub fn reader_wait(&self) -> Duration {
+        Duration::from_nanos(self.reader_wait.load(Ordering::Relaxed))
+    }
+
+    pub fn hasher_wait(&self) -> Duration {
+        Duration::from_nanos(self.hasher_wait.load(Ordering::Relaxed))
+    }
+
+    fn add(counter: &AtomicU64, waited: Duration) {
+        let nanos = u64::try_from(waited.as_nanos()).unwrap_or(u64::MAX);
+        counter.fetch_add(nanos, Ordering::Relaxed);
+    }
+}
+
+impl fmt::Display for PipelineStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "pipeline: the reader waited {:.3} s for free buffers, the hasher waited {:.3} s for data",
+            self.reader_wait().as_secs_f64(),
+            self.hasher_wait().as_secs_f64()
+        )
+    }
+}
+
+/// Returns the digest of everything `reader` yields and its size in bytes, reading on another
+/// thread through buffers of `buffer_size` bytes
+pub fn hash_pipelined(
+    mut reader: impl Read + Send,
+    mut hasher: Hasher,
+    buffer_size: usize,
+    stats: &PipelineStats,
+) -> io::Result<(Digest, u64)> {
+    thread::scope(|scope| {
+        // the channels are dropped when this closure returns, which stops the reader if hashing stops first
+        let (filled_sender, filled) =
+            mpsc::sync_channel::<io::Result<(Vec<u8>, usize)>>(NB_BUFFERS);
+        let (free_sender, free) = mpsc::sync_channel::<Vec<u8>>(NB_BUFFERS);
+
+        for _ in 0..NB_BUFFERS {
+            let _ = free_sender.send(vec![0u8; buffer_size.max(1)]);
+        }
+
+        scope.spawn(move || loop {
+            let start = Instant::now();
+            let Ok(mut buffer) = free.recv() else {
+                return;
+            };
+            PipelineStats::add(&stats.reader_wait, start.elapsed());
+
+            let result = read_some(&mut reader, &mut buffer).map(|read| (buffer, read));
+            let is_last = !matches!(result, Ok((_, read)) if read > 0);
+
+            if filled_sender.send(result).is_err() || is_last {
+                return;
+            }
+        });
+
+        let mut size = 0;
+
+        loop {
+            let start = Instant::now();
+            let received = filled.recv();
+            PipelineStats::add(&stats.hasher_wait, start.elapsed());
+
+            // the reader only hangs up after sending the end of the input or an error,
+            // or by panicking, which the scope re-raises
+            let Ok(result) = received else {
+                break;
+            };
+
+            let (buffer, read) = result?;
+
+            if read == 0 {
+                break;
+            }
+
+            hasher.update(&buffer[..read]);
+            size += read as u64;
+
+            let _ = free_sender.send(buffer);
+        }
+
+        Ok((hasher.finalize(), size))
+    })
+}
+
+fn read_some(reader: &mut impl Read, buffer: &mut [u8]) -> io::Result<usize> {
+    loop {
+        match reader.read(buffer) {
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::algorithm::Algorithm;
+    use crate::input;
+
+    /// Reader yielding its content in chunks of varying lengths, then failing if asked to
+    struct ChunkedReader {
+        content: Vec<u8>,
+        position: usize,
+        chunk_lengths: std::iter::Cycle<std::vec::IntoIter<usize>>,
+        fail_at_end: bool,
+    }
+
+    impl ChunkedReader {
+        fn new(content: &[u8], fail_at_end: bool) -> ChunkedReader {
+            ChunkedReader {
+                content: content.to_vec(),
+                position: 0,
+                chunk_lengths: vec![1, 63, 64, 65, 1000, 7].into_iter().cycle(),
+                fail_at_end,
+            }
+        }
+    }
+
+    impl Read for ChunkedReader {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let left = self.content.len() - self.position;
+
+            if left == 0 && self.fail_at_end {
+                return Err(io::Error::other("disconnected"));
+            }
+
+            let len = left
+                .min(buffer.len())
+                .min(self.chunk_lengths.next().unwrap());
+            buffer[..len].copy_from_slice(&self.content[self.position..self.position + len]);
+            self.position += len;
+
+            Ok(len)
+        }
+    }
+
+    #[test]
+    fn same_digest_as_single_threaded() {
+        let content: Vec<u8> = (0..20_000u32).map(|i| (i * 31 % 253) as u8).collect();
+        let stats = PipelineStats::default();
+
+        for len in [0, 1, 55, 64, 4096, 20_000] {
+            for buffer_size in [1, 64, 100, 1 << 16] {
+                let expected =
+                    input::hash_buffered(&content[..len], Algorithm::Sha256.hasher(), buffer_size)
+                        .unwrap();
+
+                let reader = ChunkedReader::new(&content[..len], false);
+                let pipelined =
+                    hash_pipelined(reader, Algorithm::Sha256.hasher(), buffer_size, &stats)
+                        .unwrap();
+
+                assert_eq!(
+                    pipelined, expected,
+                    "{len} bytes, {buffer_size} bytes buffers"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn read_error() {
+        let stats = PipelineStats::default();
+        let reader = ChunkedReader::new(&[0; 5000], true);
+
+        let err = hash_pipelined(reader, Algorithm::Sha256.hasher(), 128, &stats).unwrap_err();
+        assert_eq!(err.to_string(), "disconnected");
+    }
+
+    #[test]
+    fn stats_line() {
+        let stats = PipelineStats::default();
+        PipelineStats::add(&stats.reader_wait, Duration::from_millis(1500));
+        PipelineStats::add(&stats.hasher_wait, Duration::from_millis(20));
+
+        assert_eq!(
+            stats.to_string(),
+            "pipeline: the reader waited 1.500 s for free buffers, the hasher waited 0.020 s for data"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+//! Bounded worker pool hashing several inputs concurrently.
+//!
+//! Workers pick the next input from a shared counter, so at most `jobs` inputs are
+//! being read at the same time. Results are given back in the inputs order, whatever
+//! the order in which workers finish.
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+
+/// Default number of workers: the number of CPUs available
+pub fn default_jobs() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Applies `work` to every item with `jobs` workers and calls `emit` on each result, in the items order
+pub fn map_ordered<T, R>(
+    items: &[T],
+    jobs: usize,
+    work: impl Fn(&T) -> R + Sync,
+    mut emit: impl FnMut(&T, R),
+) where
+    T: Sync,
+    R: Send,
+{
+    let next = AtomicUsize::new(0);
+    let nb_workers = jobs.clamp(1, items.len().max(1));
+
+    thread::scope(|scope| {
+        let (sender, receiver) = mpsc::sync_channel(nb_workers);
+
+        for _ in 0..nb_workers {
+            let sender = sender.clone();
+            let (next, work) = (&next, &work);
+
+            scope.spawn(move || loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+
+                if index >= items.len() {
+                    break;
+                }
+
+                if sender.send((index, work(&items[index]))).is_err() {
+                    break;
+                }
+            });
+        }
+
+        drop(sender);
+
+        // results which arrived before the ones preceding them
+        let mut pending = BTreeMap::new();
+        let mut next_to_emit = 0;
+
+        for (index, result) in receiver {
+            pending.insert(index, result);
+
+            while let Some(result) = pending.remove(&next_to_emit) {
+                emit(&items[next_to_emit], result);
+                next_to_emit += 1;
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn results_in_items_order() {
+        let items: Vec<u64> = (0..20).collect();
+        let mut emitted = Vec::new();
+
+        // the first items are the slowest ones, so they finish last
+        map_ordered(
+            &items,
+            4,
+            |item| {
+                thread::sleep(Duration::from_millis(20 - item));
+                item * 2
+            },
+            |item, result| emitted.push((*item, result)),
+        );
+
+        let emitted_good: Vec<(u64, u64)> = items.iter().map(|item| (*item, item * 2)).collect();
+
+        assert_eq!(emitted, emitted_good);
+    }
+
+    #[test]
+    fn no_items() {
+        let items: Vec<u64> = Vec::new();
+        let mut nb_emitted = 0;
+
+        map_ordered(&items, 4, |item| *item, |_, _| nb_emitted += 1);
+
+        assert_eq!(nb_emitted, 0);
+    }
+}
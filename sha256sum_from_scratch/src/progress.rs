@@ -0,0 +1,248 @@
+//! `--progress` and `--stats` reporting, both written on stderr so stdout stays parseable.
+//!
+//! Progress is counted in bytes read by the hashers. When stderr is a terminal, a status line
+//! is redrawn a few times per second; otherwise a plain line is printed every few seconds.
+//! The percentage and ETA are only shown when the total size is known, which isn't the
+//! case for the standard input, special files such as block devices, or directory trees.
+
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+const TERMINAL_INTERVAL: Duration = Duration::from_millis(200);
+const PLAIN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Bytes hashed so far, shared between the workers and the reporting thread
+pub struct Progress {
+    total: Option<u64>,
+    bytes: AtomicU64,
+    start: Instant,
+    finished: Mutex<bool>,
+    wake_up: Condvar,
+}
+
+impl Progress {
+    pub fn new(total: Option<u64>) -> Progress {
+        Progress {
+            total,
+            bytes: AtomicU64::new(0),
+            start: Instant::now(),
+            finished: Mutex::new(false),
+            wake_up: Condvar::new(),
+        }
+    }
+
+    pub fn add(&self, bytes: u64) {
+        self.bytes.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    /// Writes the progress on `out` until [`Progress::finish`] is called, then writes it a last time
+    pub fn report(&self, mut out: impl Write, is_terminal: bool) {
+        let interval = if is_terminal {
+            TERMINAL_INTERVAL
+        } else {
+            PLAIN_INTERVAL
+        };
+
+        let mut finished = self.finished.lock().unwrap_or_else(|err| err.into_inner());
+
+        loop {
+            (finished, _) = self
+                .wake_up
+                .wait_timeout_while(finished, interval, |finished| !*finished)
+                .unwrap_or_else(|err| err.into_inner());
+
+            let line = status(
+                self.bytes.load(Ordering::Relaxed),
+                self.total,
+                self.start.elapsed(),
+            );
+
+            // progress is a best effort, a broken stderr must not stop hashing
+            let _ = match (is_terminal, *finished) {
+                (true, false) => write!(out, "\r\x1b[K{line}"),
+                (true, true) => writeln!(out, "\r\x1b[K{line}"),
+                (false, _) => writeln!(out, "{line}"),
+            };
+            let _ = out.flush();
+
+            if *finished {
+                return;
+            }
+        }
+    }
+
+    /// Stops [`Progress::report`]
+    pub fn finish(&self) {
+        *self.finished.lock().unwrap_or_else(|err| err.into_inner()) = true;
+        self.wake_up.notify_all();
+    }
+}
+
+/// Reader adding the bytes it yields to the progress, if any
+pub struct ProgressReader<'a, R> {
+    inner: R,
+    progress: Option<&'a Progress>,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    pub fn new(inner: R, progress: Option<&'a Progress>) -> ProgressReader<'a, R> {
+        ProgressReader { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+
+        if let Some(progress) = self.progress {
+            progress.add(read as u64);
+        }
+
+        Ok(read)
+    }
+}
+
+/// Summary printed by `--stats`
+pub struct Stats {
+    pub bytes: u64,
+    pub files: usize,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} files, {} bytes ({}) hashed in {:.3} s, {}/s on average",
+            self.files,
+            self.bytes,
+            human_bytes(self.bytes),
+            self.elapsed.as_secs_f64(),
+            human_bytes(rate(self.bytes, self.elapsed) as u64)
+        )
+    }
+}
+
+fn status(bytes: u64, total: Option<u64>, elapsed: Duration) -> String {
+    let rate = rate(bytes, elapsed);
+    let speed = format!("{}/s", human_bytes(rate as u64));
+
+    match total {
+        Some(total) if total > 0 => {
+            let percent = 100.0 * bytes.min(total) as f64 / total as f64;
+
+            let eta = match rate > 0.0 {
+                true => duration((total.saturating_sub(bytes)) as f64 / rate),
+                false => String::from("--:--:--"),
+            };
+
+            format!(
+                "{} / {} ({percent:.1}%), {speed}, ETA {eta}",
+                human_bytes(bytes),
+                human_bytes(total)
+            )
+        }
+        _ => format!("{}, {speed}", human_bytes(bytes)),
+    }
+}
+
+/// Bytes per second
+fn rate(bytes: u64, elapsed: Duration) -> f64 {
+    match elapsed.as_secs_f64() {
+        secs if secs > 0.0 => bytes as f64 / secs,
+        _ => 0.0,
+    }
+}
+
+/// Size in decimal units, such as `12.3 MB`
+fn human_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["kB", "MB", "GB", "TB", "PB"];
+
+    if bytes < 1000 {
+        return format!("{bytes} B");
+    }
+
+    let mut value = bytes as f64 / 1000.0;
+    let mut unit = 0;
+
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+
+    format!("{value:.1} {}", UNITS[unit])
+}
+
+fn duration(secs: f64) -> String {
+    let secs = secs.round() as u64;
+
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn human_bytes_test() {
+        assert_eq!(human_bytes(999), "999 B");
+        assert_eq!(human_bytes(1000), "1.0 kB");
+        assert_eq!(human_bytes(123_456_789), "123.5 MB");
+        assert_eq!(human_bytes(200_000_000_000), "200.0 GB");
+    }
+
+    #[test]
+    fn status_test() {
+        let elapsed = Duration::from_secs(10);
+
+        assert_eq!(
+            status(50_000_000, Some(200_000_000), elapsed),
+            "50.0 MB / 200.0 MB (25.0%), 5.0 MB/s, ETA 00:00:30"
+        );
+        assert_eq!(status(50_000_000, None, elapsed), "50.0 MB, 5.0 MB/s");
+        assert_eq!(
+            status(0, Some(10), Duration::ZERO),
+            "0 B / 10 B (0.0%), 0 B/s, ETA --:--:--"
+        );
+    }
+
+    #[test]
+    fn stats_display() {
+        let stats = Stats {
+            bytes: 3_000_000,
+            files: 2,
+            elapsed: Duration::from_millis(1500),
+        };
+
+        assert_eq!(
+            stats.to_string(),
+            "2 files, 3000000 bytes (3.0 MB) hashed in 1.500 s, 2.0 MB/s on average"
+        );
+    }
+
+    #[test]
+    fn reader_counts_bytes() {
+        let progress = Progress::new(Some(5));
+
+        let mut content = Vec::new();
+        ProgressReader::new(&b"hello"[..], Some(&progress))
+            .read_to_end(&mut content)
+            .unwrap();
+
+        assert_eq!(progress.bytes.load(Ordering::Relaxed), 5);
+
+        let mut out = Vec::new();
+        thread::scope(|scope| {
+            scope.spawn(|| progress.report(&mut out, false));
+            progress.finish();
+        });
+
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("5 B / 5 B (100.0%)"));
+        assert!(out.ends_with('\n'));
+    }
+}
@@ -0,0 +1,51 @@
+//! Human-friendly byte sizes given on the command line.
+//!
+//! A size is an integer optionally followed by a unit: `K`, `M`, `G` and `T` (or `KiB`, `MiB`,
+//! `GiB` and `TiB`) are powers of 1024, `KB`, `MB`, `GB` and `TB` are powers of 1000, like dd(1) does.
+
+/// Parses a size such as `4096`, `64K` or `2GiB` into bytes
+pub fn parse_size(value: &str) -> Option<u64> {
+    let digits_end = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (number, unit) = value.split_at(digits_end);
+
+    let multiplier: u64 = match unit {
+        "" | "B" => 1,
+        "K" | "KiB" => 1 << 10,
+        "M" | "MiB" => 1 << 20,
+        "G" | "GiB" => 1 << 30,
+        "T" | "TiB" => 1 << 40,
+        "KB" | "kB" => 1_000,
+        "MB" => 1_000_000,
+        "GB" => 1_000_000_000,
+        "TB" => 1_000_000_000_000,
+        _ => return None,
+    };
+
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_size_test() {
+        assert_eq!(parse_size("0"), Some(0));
+        assert_eq!(parse_size("4096"), Some(4096));
+        assert_eq!(parse_size("512B"), Some(512));
+        assert_eq!(parse_size("64K"), Some(64 * 1024));
+        assert_eq!(parse_size("64KiB"), Some(64 * 1024));
+        assert_eq!(parse_size("3M"), Some(3 * 1024 * 1024));
+        assert_eq!(parse_size("2GiB"), Some(2 << 30));
+        assert_eq!(parse_size("1MB"), Some(1_000_000));
+
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("K"), None);
+        assert_eq!(parse_size("+1"), None);
+        assert_eq!(parse_size("1.5G"), None);
+        assert_eq!(parse_size("1X"), None);
+        assert_eq!(parse_size("20000000T"), None); // overflows u64
+    }
+}
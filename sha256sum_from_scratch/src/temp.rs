@@ -0,0 +1,22 @@
+//! Unique paths in the temporary directory, for the bench file and the test fixtures.
+
+use std::path::PathBuf;
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Path in the temporary directory that no other call returns: the process id alone isn't
+/// enough, since ids are reused by processes run one after the other
+pub(crate) fn temp_path(name: &str) -> PathBuf {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.subsec_nanos());
+
+    std::env::temp_dir().join(format!(
+        "sha256sum_from_scratch_{name}_{}_{nanos}_{}",
+        process::id(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    ))
+}
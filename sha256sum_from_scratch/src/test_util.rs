@@ -0,0 +1,14 @@
+//! Fixtures shared by the unit tests.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::temp::temp_path;
+
+/// New empty directory for a test
+pub fn new_test_dir(name: &str) -> PathBuf {
+    let dir = temp_path(name);
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
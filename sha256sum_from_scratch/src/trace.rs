@@ -0,0 +1,263 @@
+//! `trace` subcommand: every intermediate value of SHA-256 for a short message, to follow the
+//! algorithm step by step, see [`sha_256_scratch::trace`].
+//!
+//! `trace [--json] (--string <TEXT> | <FILE>)` prints, for each block of the padded message,
+//! its bytes, the message schedule W[0..64], a row per round with W[t], K[t], T1, T2 and the
+//! working variables a..h after it, then the chaining value. `-` reads the message from stdin.
+
+use crate::error::Error;
+use crate::output::{hex, json_string};
+use crate::{option_value, split_option};
+use sha_256_scratch::trace::{BlockTrace, Trace};
+use sha_256_scratch::WorkingVariables;
+use std::fs;
+use std::io::{self, Read, Write};
+
+/// Message given on the command line, or read from a file
+#[derive(Debug, PartialEq)]
+pub enum MessageInput {
+    String(String),
+    File(String), // "-" for stdin
+}
+
+impl MessageInput {
+    pub fn read(&self) -> Result<Vec<u8>, Error> {
+        let msg = match self {
+            MessageInput::String(text) => text.clone().into_bytes(),
+            MessageInput::File(path) if path == "-" => {
+                let mut msg = Vec::new();
+                io::stdin().lock().read_to_end(&mut msg)?;
+                msg
+            }
+            MessageInput::File(path) => fs::read(path).map_err(|err| Error::io(path, err))?,
+        };
+
+        Ok(msg)
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct TraceCommand {
+    pub input: MessageInput,
+    pub json: bool,
+}
+
+impl TraceCommand {
+    /// Parses the arguments following `trace`
+    pub fn parse_args(
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<TraceCommand, &'static str> {
+        let mut inputs = Vec::new();
+        let mut json = false;
+
+        while let Some(arg) = args.next() {
+            if arg == "-" || !arg.starts_with('-') {
+                inputs.push(MessageInput::File(arg));
+                continue;
+            }
+
+            let (name, inline_value) = split_option(&arg);
+
+            match name {
+                "--json" => json = true,
+                "--string" => inputs.push(MessageInput::String(
+                    option_value(inline_value, &mut args).ok_or("No string has been provided")?,
+                )),
+                _ => return Err("Unknown trace option"),
+            }
+        }
+
+        let [input] = <[MessageInput; 1]>::try_from(inputs)
+            .map_err(|_| "trace expects one file or --string")?;
+
+        Ok(TraceCommand { input, json })
+    }
+
+    pub fn run(&self) -> Result<(), Error> {
+        let trace = Trace::new(&self.input.read()?);
+        let mut out = io::stdout().lock();
+
+        match self.json {
+            true => write_json(&trace, &mut out)?,
+            false => write_table(&trace, &mut out)?,
+        }
+
+        Ok(())
+    }
+}
+
+pub fn write_table(trace: &Trace, out: &mut impl Write) -> io::Result<()> {
+    for (index, block) in trace.blocks.iter().enumerate() {
+        writeln!(out, "block {index}: {}", hex(&block.block))?;
+        writeln!(out)?;
+
+        for (row, words) in block.schedule.chunks(8).enumerate() {
+            writeln!(
+                out,
+                "W[{:2}..{:2}]  {}",
+                row * 8,
+                row * 8 + 8,
+                words_hex(words)
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(
+            out,
+            " t  W[t]     K[t]     T1       T2       a        b        c        d        e        f        g        h"
+        )?;
+        writeln!(out, "{:40}{}", "init", vars_hex(&block.initial))?;
+
+        for round in &block.rounds {
+            writeln!(
+                out,
+                "{:2}  {:08x} {:08x} {:08x} {:08x} {}",
+                round.t,
+                round.w,
+                round.k,
+                round.t1,
+                round.t2,
+                vars_hex(&round.vars)
+            )?;
+        }
+
+        writeln!(out)?;
+        writeln!(out, "chaining value  {}", words_hex(&block.chaining_value))?;
+        writeln!(out)?;
+    }
+
+    match trace.digest() {
+        Some(digest) => writeln!(out, "digest  {digest}"),
+        None => Ok(()),
+    }
+}
+
+pub fn write_json(trace: &Trace, out: &mut impl Write) -> io::Result<()> {
+    let blocks: Vec<String> = trace.blocks.iter().map(block_json).collect();
+
+    writeln!(
+        out,
+        "{{\"blocks\":[{}],\"digest\":{}}}",
+        blocks.join(","),
+        trace
+            .digest()
+            .map_or(String::from("null"), |digest| json_string(&digest.to_hex()))
+    )
+}
+
+fn block_json(block: &BlockTrace) -> String {
+    let rounds: Vec<String> = block
+        .rounds
+        .iter()
+        .map(|round| {
+            format!(
+                "{{\"t\":{},\"w\":\"{:08x}\",\"k\":\"{:08x}\",\"t1\":\"{:08x}\",\"t2\":\"{:08x}\",{}}}",
+                round.t,
+                round.w,
+                round.k,
+                round.t1,
+                round.t2,
+                vars_json(&round.vars)
+            )
+        })
+        .collect();
+
+    format!(
+        "{{\"block\":\"{}\",\"schedule\":{},\"initial\":{{{}}},\"rounds\":[{}],\"chaining_value\":{}}}",
+        hex(&block.block),
+        words_json(&block.schedule),
+        vars_json(&block.initial),
+        rounds.join(","),
+        words_json(&block.chaining_value)
+    )
+}
+
+fn words_hex(words: &[u32]) -> String {
+    let words: Vec<String> = words.iter().map(|word| format!("{word:08x}")).collect();
+
+    words.join(" ")
+}
+
+fn vars_hex(vars: &WorkingVariables) -> String {
+    let vars: Vec<u32> = vars.iter().copied().collect();
+
+    words_hex(&vars)
+}
+
+fn words_json(words: &[u32]) -> String {
+    let words: Vec<String> = words.iter().map(|word| format!("\"{word:08x}\"")).collect();
+
+    format!("[{}]", words.join(","))
+}
+
+fn vars_json(vars: &WorkingVariables) -> String {
+    let fields: Vec<String> = ["a", "b", "c", "d", "e", "f", "g", "h"]
+        .iter()
+        .zip(vars.iter())
+        .map(|(name, var)| format!("\"{name}\":\"{var:08x}\""))
+        .collect();
+
+    fields.join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<TraceCommand, &'static str> {
+        TraceCommand::parse_args(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn parse_args_test() {
+        let command_good = TraceCommand {
+            input: MessageInput::String(String::from("abc")),
+            json: true,
+        };
+        assert_eq!(parse(&["--json", "--string", "abc"]), Ok(command_good));
+
+        assert_eq!(
+            parse(&["-"]).unwrap().input,
+            MessageInput::File(String::from("-"))
+        );
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--string=abc", "file"]).is_err());
+        assert!(parse(&["--rounds", "file"]).is_err());
+    }
+
+    #[test]
+    fn table_abc() {
+        let mut out = Vec::new();
+        write_table(&Trace::new(b"abc"), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        let lines: Vec<&str> = out.lines().collect();
+        assert!(lines[0].starts_with("block 0: 6162638000"));
+        assert_eq!(
+            lines[2],
+            "W[ 0.. 8]  61626380 00000000 00000000 00000000 00000000 00000000 00000000 00000000"
+        );
+        assert!(out.contains("\n 0  61626380 428a2f98 "));
+        assert!(out.contains(
+            " 5d6aebcd 6a09e667 bb67ae85 3c6ef372 fa2a4622 510e527f 9b05688c 1f83d9ab\n"
+        ));
+        assert_eq!(
+            lines.last().unwrap(),
+            &"digest  ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn json_abc() {
+        let mut out = Vec::new();
+        write_json(&Trace::new(b"abc"), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+
+        assert!(out.starts_with("{\"blocks\":[{\"block\":\"61626380"));
+        assert!(out.contains("{\"t\":63,\"w\":\"12b1edeb\",\"k\":\"c67178f2\","));
+        assert!(out.contains("\"a\":\"506e3058\",\"b\":\"d39a2165\""));
+        assert!(out.ends_with(
+            "\"digest\":\"ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\"}\n"
+        ));
+    }
+}
@@ -0,0 +1,286 @@
+//! Deterministic digest of a whole directory tree.
+//!
+//! The digest is the SHA-256 of a canonical byte encoding of the tree, so it only depends
+//! on the tree's paths, entry types, executable bits and file contents. It doesn't depend on
+//! the machine, the filesystem enumeration order, timestamps, owners or other permission bits.
+//!
+//! # Encoding (version 1)
+//!
+//! The encoding starts with the ASCII header `sha256sum_from_scratch tree v1\n`, followed by
+//! one record per entry below the root directory (the root itself has no record).
+//! Records are sorted by their path, compared byte by byte.
+//!
+//! ~~~text
+//! record = type SP mode SP [digest SP] path NUL
+//! type   = "d" (directory) / "f" (regular file) / "l" (symbolic link)
+//! mode   = "x" (regular file with owner execute bit set) / "-" (anything else)
+//! digest = 64 lowercase hex digits, present for "f" and "l" only
+//! path   = path relative to the root, components separated by "/"
+//! ~~~
+//!
+//! - the digest of a regular file is the SHA-256 of its content
+//! - the digest of a symbolic link is the SHA-256 of its target path, links are never followed
+//! - paths are the raw bytes of the file names on Unix, and must be valid UTF-8 elsewhere
+//! - any other entry type (socket, FIFO, device...) makes the whole computation fail
+//!
+//! The tree digest is the SHA-256 of the concatenation of the header and all records,
+//! written as 64 lowercase hex digits. The tree size is the sum of the regular files sizes.
+
+use crate::algorithm::Algorithm;
+use sha_256_scratch::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const HEADER: &[u8] = b"sha256sum_from_scratch tree v1\n";
+
+#[derive(Debug, PartialEq)]
+enum EntryType {
+    Directory,
+    File { executable: bool, digest: String },
+    Symlink { digest: String },
+}
+
+#[derive(Debug, PartialEq)]
+struct Entry {
+    path: Vec<u8>,
+    entry_type: EntryType,
+}
+
+/// Computes the tree digest of `root`, as specified in this module documentation, and the tree size
+pub fn tree_digest(root: &Path) -> io::Result<(Digest, u64)> {
+    let (encoding, size) = encode_tree(root)?;
+
+    let mut hasher = Sha256::new();
+    hasher.update(&encoding);
+
+    Ok((hasher.finalize(), size))
+}
+
+fn encode_tree(root: &Path) -> io::Result<(Vec<u8>, u64)> {
+    let mut entries = Vec::new();
+    let mut size = 0;
+    collect_entries(root, &[], &mut entries, &mut size)?;
+
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+
+    let mut encoding = HEADER.to_vec();
+
+    for entry in entries {
+        encode_entry(&entry, &mut encoding);
+    }
+
+    Ok((encoding, size))
+}
+
+fn encode_entry(entry: &Entry, out: &mut Vec<u8>) {
+    match &entry.entry_type {
+        EntryType::Directory => out.extend_from_slice(b"d - "),
+        EntryType::File { executable, digest } => {
+            out.extend_from_slice(if *executable { b"f x " } else { b"f - " });
+            out.extend_from_slice(digest.as_bytes());
+            out.push(b' ');
+        }
+        EntryType::Symlink { digest } => {
+            out.extend_from_slice(b"l - ");
+            out.extend_from_slice(digest.as_bytes());
+            out.push(b' ');
+        }
+    }
+
+    out.extend_from_slice(&entry.path);
+    out.push(0);
+}
+
+fn collect_entries(
+    dir: &Path,
+    prefix: &[u8],
+    entries: &mut Vec<Entry>,
+    size: &mut u64,
+) -> io::Result<()> {
+    for dir_entry in fs::read_dir(dir)? {
+        let dir_entry = dir_entry?;
+        let path = dir_entry.path();
+
+        let mut relative = prefix.to_vec();
+        if !relative.is_empty() {
+            relative.push(b'/');
+        }
+        relative.extend_from_slice(&name_bytes(&dir_entry.file_name())?);
+
+        let metadata = fs::symlink_metadata(&path)?;
+        let file_type = metadata.file_type();
+
+        if file_type.is_dir() {
+            collect_entries(&path, &relative, entries, size)?;
+
+            entries.push(Entry {
+                path: relative,
+                entry_type: EntryType::Directory,
+            });
+        } else if file_type.is_file() {
+            let (digest, file_size) = crate::input::hash_file(&path, Algorithm::Sha256.hasher())?;
+            *size += file_size;
+
+            entries.push(Entry {
+                path: relative,
+                entry_type: EntryType::File {
+                    executable: is_executable(&metadata),
+                    digest: digest.to_hex(),
+                },
+            });
+        } else if file_type.is_symlink() {
+            let target = fs::read_link(&path)?;
+            let digest = sha_256_scratch::sha_256(name_bytes(target.as_os_str())?);
+
+            entries.push(Entry {
+                path: relative,
+                entry_type: EntryType::Symlink { digest },
+            });
+        } else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsupported file type: {}", path.display()),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn name_bytes(name: &std::ffi::OsStr) -> io::Result<Vec<u8>> {
+    use std::os::unix::ffi::OsStrExt;
+
+    Ok(name.as_bytes().to_vec())
+}
+
+#[cfg(not(unix))]
+fn name_bytes(name: &std::ffi::OsStr) -> io::Result<Vec<u8>> {
+    match name.to_str() {
+        Some(name) => Ok(name.replace('\\', "/").into_bytes()),
+        None => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("path is not valid UTF-8: {}", name.to_string_lossy()),
+        )),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(metadata: &fs::Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    metadata.permissions().mode() & 0o100 != 0
+}
+
+#[cfg(not(unix))]
+fn is_executable(_metadata: &fs::Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::test_util::new_test_dir;
+
+    ////////////////// unit tests
+    #[test]
+    fn encode_tree_test() {
+        let dir = new_test_dir("tree_encoding");
+        fs::create_dir(dir.join("sub")).unwrap();
+        fs::write(dir.join("sub").join("hi"), "hi").unwrap();
+        fs::write(dir.join("empty"), "").unwrap();
+
+        let (encoding, size) = encode_tree(&dir).unwrap();
+
+        assert_eq!(size, 2);
+
+        let mut encoding_good = HEADER.to_vec();
+        encoding_good.extend_from_slice(
+            b"f - e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855 empty\0",
+        );
+        encoding_good.extend_from_slice(b"d - sub\0");
+        encoding_good.extend_from_slice(
+            b"f - 8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4 sub/hi\0",
+        );
+
+        assert_eq!(encoding, encoding_good);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn tree_digest_independent_of_creation_order() {
+        let first = new_test_dir("tree_order_first");
+        fs::create_dir(first.join("b")).unwrap();
+        fs::write(first.join("b").join("c"), "c").unwrap();
+        fs::write(first.join("a"), "a").unwrap();
+
+        let second = new_test_dir("tree_order_second");
+        fs::write(second.join("a"), "a").unwrap();
+        fs::create_dir(second.join("b")).unwrap();
+        fs::write(second.join("b").join("c"), "c").unwrap();
+
+        assert_eq!(tree_digest(&first).unwrap(), tree_digest(&second).unwrap());
+
+        fs::remove_dir_all(first).unwrap();
+        fs::remove_dir_all(second).unwrap();
+    }
+
+    #[test]
+    fn tree_digest_empty_directory_counts() {
+        let dir = new_test_dir("tree_empty_dir");
+        let before = tree_digest(&dir).unwrap();
+
+        fs::create_dir(dir.join("empty")).unwrap();
+        let after = tree_digest(&dir).unwrap();
+
+        assert_ne!(before, after);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tree_digest_executable_bit() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = new_test_dir("tree_executable");
+        let script = dir.join("script");
+        fs::write(&script, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o644)).unwrap();
+        let before = tree_digest(&dir).unwrap();
+
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        let after = tree_digest(&dir).unwrap();
+
+        // other permission bits are ignored
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o700)).unwrap();
+        let after_group_change = tree_digest(&dir).unwrap();
+
+        assert_ne!(before, after);
+        assert_eq!(after, after_group_change);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn tree_digest_symlink_not_followed() {
+        let dir = new_test_dir("tree_symlink");
+        fs::write(dir.join("target"), "content").unwrap();
+        std::os::unix::fs::symlink("target", dir.join("link")).unwrap();
+
+        let (encoding, _) = encode_tree(&dir).unwrap();
+        let link_digest = sha_256_scratch::sha_256(b"target".to_vec());
+
+        let mut record = b"l - ".to_vec();
+        record.extend_from_slice(link_digest.as_bytes());
+        record.extend_from_slice(b" link\0");
+
+        assert!(encoding.windows(record.len()).any(|w| w == record));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
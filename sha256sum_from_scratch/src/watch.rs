@@ -0,0 +1,238 @@
+//! `--watch`: hashes the given files, then again whenever one of them is written.
+//!
+//! The parent directories are watched with Linux inotify rather than the files themselves, so
+//! files replaced by a rename, as editors and build tools often do, keep being watched.
+//! A file is rehashed when it is closed after being written, or when another file is renamed to its path.
+//!
+//! Each digest is printed with a UTC timestamp, and marked `(changed)` when it differs from the
+//! previous digest printed for the same file.
+
+use crate::error::Error;
+use sha_256_scratch::Digest;
+use std::io::{self, Write};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(target_os = "linux")]
+use crate::inotify::{Inotify, IN_CLOSE_WRITE, IN_MOVED_TO, IN_Q_OVERFLOW};
+#[cfg(target_os = "linux")]
+use std::{collections::BTreeSet, ffi::OsString, path::Path};
+
+/// Prints the digest of every file, then watches them until an error occurs
+#[cfg(target_os = "linux")]
+pub fn watch(
+    filenames: &[String],
+    hash: impl Fn(&String) -> io::Result<(Digest, u64)>,
+    out: &mut impl Write,
+) -> Result<(), Error> {
+    // watching starts before the first hashes, so no write in between is missed
+    let mut watcher = Watcher::new(filenames)?;
+    let mut last_digests = vec![None; filenames.len()];
+
+    for (filename, last_digest) in filenames.iter().zip(&mut last_digests) {
+        report(filename, hash(filename), last_digest, out)?;
+    }
+
+    loop {
+        for index in watcher.next_changes()? {
+            let filename = &filenames[index];
+            report(filename, hash(filename), &mut last_digests[index], out)?;
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn watch(
+    _filenames: &[String],
+    _hash: impl Fn(&String) -> io::Result<(Digest, u64)>,
+    _out: &mut impl Write,
+) -> Result<(), Error> {
+    Err(Error::Usage(
+        "--watch relies on inotify, which is only available on Linux",
+    ))
+}
+
+#[cfg(target_os = "linux")]
+pub struct Watcher {
+    inotify: Inotify,
+    files: Vec<(i32, OsString)>, // watch descriptor of the parent directory and file name, in the inputs order
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    pub fn new(filenames: &[String]) -> io::Result<Watcher> {
+        let inotify = Inotify::new()?;
+        let mut files = Vec::new();
+
+        for filename in filenames {
+            let path = Path::new(filename);
+
+            let name = path.file_name().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{filename}: not a file path"),
+                )
+            })?;
+
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+
+            // watching the same directory twice gives back the same descriptor
+            let wd = inotify
+                .add_watch(dir, IN_CLOSE_WRITE | IN_MOVED_TO)
+                .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", dir.display())))?;
+
+            files.push((wd, name.to_os_string()));
+        }
+
+        Ok(Watcher { inotify, files })
+    }
+
+    /// Blocks until some of the files are written, returns their indexes in the inputs
+    pub fn next_changes(&mut self) -> io::Result<Vec<usize>> {
+        loop {
+            let mut changed = BTreeSet::new();
+
+            for event in self.inotify.read_events()? {
+                // events were lost, any file may have changed
+                if event.mask & IN_Q_OVERFLOW != 0 {
+                    changed.extend(0..self.files.len());
+                    continue;
+                }
+
+                for (index, (wd, name)) in self.files.iter().enumerate() {
+                    if event.wd == *wd && event.name.as_ref() == Some(name) {
+                        changed.insert(index);
+                    }
+                }
+            }
+
+            if !changed.is_empty() {
+                return Ok(changed.into_iter().collect());
+            }
+        }
+    }
+}
+
+fn report(
+    filename: &str,
+    result: io::Result<(Digest, u64)>,
+    last_digest: &mut Option<Digest>,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let now = timestamp(SystemTime::now());
+
+    match result {
+        Ok((digest, _)) => {
+            let marker = match last_digest {
+                Some(last_digest) if *last_digest != digest => "  (changed)",
+                _ => "",
+            };
+
+            writeln!(out, "{now}  {digest}  {filename}{marker}")?;
+            out.flush()?;
+
+            *last_digest = Some(digest);
+        }
+        // the file may be written again, so watching goes on
+        Err(err) => eprintln!("{now}  {filename}: {err}"),
+    }
+
+    Ok(())
+}
+
+/// ISO 8601 UTC timestamp with milliseconds, such as `2024-03-01T12:00:00.000Z`
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // days to civil date, from Howard Hinnant's "chrono-Compatible Low-Level Date Algorithms"
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let day_of_era = z % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153; // months starting from March
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}.{:03}Z",
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::new_test_dir;
+    use std::time::Duration;
+
+    #[test]
+    fn timestamp_test() {
+        let at = |secs, millis| {
+            timestamp(UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis))
+        };
+
+        assert_eq!(at(0, 0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(at(951_782_400, 0), "2000-02-29T00:00:00.000Z");
+        assert_eq!(at(1_700_000_000, 42), "2023-11-14T22:13:20.042Z");
+        assert_eq!(at(4_107_542_399, 999), "2100-02-28T23:59:59.999Z");
+    }
+
+    #[test]
+    fn report_marks_changes() {
+        let digest = |content: &[u8]| {
+            let mut hasher = sha_256_scratch::Sha256::new();
+            hasher.update(content);
+            hasher.finalize()
+        };
+
+        let mut out = Vec::new();
+        let mut last_digest = None;
+
+        report("f", Ok((digest(b"a"), 1)), &mut last_digest, &mut out).unwrap();
+        report("f", Ok((digest(b"a"), 1)), &mut last_digest, &mut out).unwrap();
+        report("f", Ok((digest(b"b"), 1)), &mut last_digest, &mut out).unwrap();
+
+        let out = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = out.lines().collect();
+
+        assert!(lines[0].ends_with(&format!("  {}  f", digest(b"a"))));
+        assert!(lines[1].ends_with(&format!("  {}  f", digest(b"a"))));
+        assert!(lines[2].ends_with(&format!("  {}  f  (changed)", digest(b"b"))));
+        assert_eq!(last_digest, Some(digest(b"b")));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn watcher_sees_writes_and_renames() {
+        let dir = new_test_dir("watch");
+
+        let filenames = ["a", "b"].map(|name| dir.join(name).to_string_lossy().into_owned());
+        std::fs::write(&filenames[0], "a").unwrap();
+
+        let mut watcher = Watcher::new(&filenames).unwrap();
+
+        std::fs::write(dir.join("unwatched"), "").unwrap();
+        std::fs::write(&filenames[0], "aa").unwrap();
+        assert_eq!(watcher.next_changes().unwrap(), vec![0]);
+
+        std::fs::write(dir.join("b.tmp"), "b").unwrap();
+        std::fs::rename(dir.join("b.tmp"), &filenames[1]).unwrap();
+        assert_eq!(watcher.next_changes().unwrap(), vec![1]);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+//!    This module hashes `tokio::io::AsyncRead` streams with the incremental `Sha256` hasher,
+//!    so a body can be hashed as it arrives without blocking the executor.
+//!    It is only available with the `async` feature.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncReadExt, ReadBuf};
+
+use crate::hasher::{Digest, Sha256};
+
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Reader hashing everything read through it, for streams that are also consumed elsewhere
+pub struct AsyncHashingReader<R> {
+    inner: R,
+    hasher: Sha256,
+    len: u64, // bytes read so far
+}
+
+impl<R> AsyncHashingReader<R> {
+    pub fn new(inner: R) -> AsyncHashingReader<R> {
+        Self {
+            inner,
+            hasher: Sha256::new(),
+            len: 0,
+        }
+    }
+
+    /// Number of bytes read so far
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// SHA-256 digest of the bytes read so far, and the inner reader
+    pub fn finalize(self) -> (Digest, R) {
+        (self.hasher.finalize(), self.inner)
+    }
+}
+
+impl<R: AsyncRead + Unpin> AsyncRead for AsyncHashingReader<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled_before = buf.filled().len();
+
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        let read = &buf.filled()[filled_before..];
+        this.hasher.update(read);
+        this.len += read.len() as u64;
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// Reads `reader` to its end and returns the SHA-256 digest of its content
+pub async fn hash_async_reader<R: AsyncRead + Unpin>(reader: R) -> io::Result<Digest> {
+    let mut reader = AsyncHashingReader::new(reader);
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    while reader.read(&mut buffer).await? > 0 {}
+
+    Ok(reader.finalize().0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    /// Deterministic pseudo-random numbers (xorshift64), so failures can be replayed
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+    }
+
+    fn random_message(rng: &mut Rng, len: usize) -> Vec<u8> {
+        (0..len).map(|_| rng.next() as u8).collect()
+    }
+
+    #[tokio::test]
+    async fn randomly_split_duplex_stream() {
+        let mut rng = Rng(0x2545f4914f6cdd1d);
+
+        for len in [0, 1, 55, 56, 64, 1000, 100_000] {
+            let msg = random_message(&mut rng, len);
+
+            // chunks of 1 to 200 bytes, through a pipe smaller than most of them
+            let mut chunks = Vec::new();
+            let mut rest = &msg[..];
+            while !rest.is_empty() {
+                let chunk_len = (rng.next() % 200 + 1).min(rest.len() as u64) as usize;
+                let (chunk, tail) = rest.split_at(chunk_len);
+                chunks.push(chunk.to_vec());
+                rest = tail;
+            }
+
+            let (mut client, server) = tokio::io::duplex(64);
+
+            let write = async move {
+                for chunk in chunks {
+                    client.write_all(&chunk).await.unwrap();
+                }
+                // dropping the client ends the stream
+            };
+
+            let (digest, ()) = tokio::join!(hash_async_reader(server), write);
+
+            assert_eq!(digest.unwrap().to_hex(), crate::sha_256(msg), "{len} bytes");
+        }
+    }
+
+    #[tokio::test]
+    async fn hashing_reader_passes_data_through() {
+        let msg = random_message(&mut Rng(42), 5000);
+        let mut reader = AsyncHashingReader::new(&msg[..]);
+
+        let mut copy = Vec::new();
+        reader.read_to_end(&mut copy).await.unwrap();
+        assert_eq!(copy, msg);
+        assert_eq!(reader.len(), 5000);
+
+        let (digest, _) = reader.finalize();
+        assert_eq!(digest.to_hex(), crate::sha_256(msg));
+    }
+}
@@ -0,0 +1,241 @@
+//!    This module derives the constants hardcoded in `math` from their definition, so they can be
+//!    audited instead of trusted:
+//!    - H(0) of SHA-256: first 32 bits of the fractional parts of the square roots of the first 8 primes
+//!    - H(0) of SHA-224: second 32 bits of the fractional parts of the square roots of the 9th through 16th primes
+//!    - K: first 32 bits of the fractional parts of the cube roots of the first 64 primes
+//!    - H(0) of SHA-512: first 64 bits of the fractional parts of the square roots of the first 8 primes
+//!
+//!    Roots are computed with exact integer arithmetic, one bit at a time, never with floats.
+
+use crate::error::Error;
+
+const MAX_SQRT_BITS: u32 = 64;
+const MAX_CBRT_BITS: u32 = 32;
+// far more than the 64 primes of the constants, and still quick to find by trial division
+const MAX_PRIMES: usize = 1 << 16;
+
+/// First `n` prime numbers (up to 65536), by trial division
+pub fn first_primes(n: usize) -> Result<Vec<u32>, Error> {
+    match n <= MAX_PRIMES {
+        true => Ok(primes(n)),
+        false => Err(Error::TooManyPrimes {
+            count: n,
+            max: MAX_PRIMES,
+        }),
+    }
+}
+
+fn primes(n: usize) -> Vec<u32> {
+    let mut primes: Vec<u32> = Vec::new();
+    let mut candidate: u32 = 2;
+
+    while primes.len() < n {
+        let is_prime = primes
+            .iter()
+            .take_while(|&&prime| (prime as u64) * (prime as u64) <= candidate as u64)
+            .all(|&prime| candidate % prime != 0);
+
+        if is_prime {
+            primes.push(candidate);
+        }
+
+        // the 65536th prime is far below 2^32
+        candidate += 1;
+    }
+
+    primes
+}
+
+/// First `bits` bits (up to 64) of the fractional part of the square root of `n`
+pub fn fractional_sqrt(n: u32, bits: u32) -> Result<u64, Error> {
+    check_precision(bits, MAX_SQRT_BITS)?;
+
+    Ok(sqrt_bits(n, bits))
+}
+
+/// First `bits` bits (up to 32) of the fractional part of the cube root of `n`
+pub fn fractional_cbrt(n: u32, bits: u32) -> Result<u64, Error> {
+    check_precision(bits, MAX_CBRT_BITS)?;
+
+    Ok(cbrt_bits(n, bits))
+}
+
+fn check_precision(bits: u32, max: u32) -> Result<(), Error> {
+    match bits <= max {
+        true => Ok(()),
+        false => Err(Error::UnsupportedPrecision { bits, max }),
+    }
+}
+
+fn sqrt_bits(n: u32, bits: u32) -> u64 {
+    // integer part
+    let n = n as u128;
+    let mut x: u128 = 0;
+
+    while (x + 1) * (x + 1) <= n {
+        x += 1;
+    }
+
+    // x = floor(sqrt(n) * 2^i) and r = n * 4^i - x^2 for i bits, so appending a 1 bit
+    // to x is possible when (2x + 1)^2 <= 4 * n * 4^i, that is when 4x + 1 <= 4r
+    let mut r = n - x * x;
+
+    for _ in 0..bits {
+        let step = 4 * x + 1;
+
+        if 4 * r >= step {
+            r = 4 * r - step;
+            x = 2 * x + 1;
+        } else {
+            r *= 4;
+            x *= 2;
+        }
+    }
+
+    fractional_bits(x, bits)
+}
+
+fn cbrt_bits(n: u32, bits: u32) -> u64 {
+    // integer part
+    let n = n as u128;
+    let mut x: u128 = 0;
+
+    while (x + 1) * (x + 1) * (x + 1) <= n {
+        x += 1;
+    }
+
+    // x = floor(cbrt(n) * 2^i) and r = n * 8^i - x^3 for i bits, so appending a 1 bit
+    // to x is possible when (2x + 1)^3 <= 8 * n * 8^i, that is when 12x^2 + 6x + 1 <= 8r
+    let mut r = n - x * x * x;
+
+    for _ in 0..bits {
+        let step = 12 * x * x + 6 * x + 1;
+
+        if 8 * r >= step {
+            r = 8 * r - step;
+            x = 2 * x + 1;
+        } else {
+            r *= 8;
+            x *= 2;
+        }
+    }
+
+    fractional_bits(x, bits)
+}
+
+fn fractional_bits(root: u128, bits: u32) -> u64 {
+    (root & ((1u128 << bits) - 1)) as u64
+}
+
+pub fn sha_256_h_0() -> [u32; 8] {
+    let mut h_0 = [0u32; 8];
+
+    for (h, prime) in h_0.iter_mut().zip(primes(8)) {
+        *h = sqrt_bits(prime, 32) as u32;
+    }
+
+    h_0
+}
+
+pub fn sha_224_h_0() -> [u32; 8] {
+    let mut h_0 = [0u32; 8];
+
+    for (h, prime) in h_0.iter_mut().zip(primes(16).into_iter().skip(8)) {
+        // the second 32 bits are the low half of the first 64 ones
+        *h = sqrt_bits(prime, 64) as u32;
+    }
+
+    h_0
+}
+
+pub fn sha_256_k() -> [u32; 64] {
+    let mut k = [0u32; 64];
+
+    for (k, prime) in k.iter_mut().zip(primes(64)) {
+        *k = cbrt_bits(prime, 32) as u32;
+    }
+
+    k
+}
+
+pub fn sha_512_h_0() -> [u64; 8] {
+    let mut h_0 = [0u64; 8];
+
+    for (h, prime) in h_0.iter_mut().zip(primes(8)) {
+        *h = sqrt_bits(prime, 64);
+    }
+
+    h_0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math;
+
+    #[test]
+    fn primes() {
+        let primes = first_primes(64).unwrap();
+
+        assert_eq!(&primes[..10], &[2, 3, 5, 7, 11, 13, 17, 19, 23, 29]);
+        assert_eq!(primes[63], 311);
+
+        assert_eq!(first_primes(1 << 16).unwrap()[(1 << 16) - 1], 821641);
+        assert_eq!(
+            first_primes(usize::MAX),
+            Err(Error::TooManyPrimes {
+                count: usize::MAX,
+                max: 1 << 16
+            })
+        );
+    }
+
+    #[test]
+    fn roots() {
+        // sqrt(2) = 1.0110 1010 0000 1001 ..., cbrt(2) = 1.0100 0010 1000 1010 ...
+        assert_eq!(fractional_sqrt(2, 16).unwrap(), 0x6a09);
+        assert_eq!(fractional_cbrt(2, 16).unwrap(), 0x428a);
+
+        // perfect powers have no fractional part
+        assert_eq!(fractional_sqrt(49, 64).unwrap(), 0);
+        assert_eq!(fractional_cbrt(27, 32).unwrap(), 0);
+
+        // sqrt(2) = 1.6a09e667f3bcc908b2fb1366..., the last bit must not be rounded
+        assert_eq!(fractional_sqrt(2, 4).unwrap(), 0x6);
+        assert_eq!(fractional_sqrt(2, 64).unwrap(), 0x6a09e667f3bcc908);
+
+        assert_eq!(
+            fractional_sqrt(2, 65),
+            Err(Error::UnsupportedPrecision { bits: 65, max: 64 })
+        );
+        assert!(fractional_cbrt(2, 33).is_err());
+    }
+
+    #[test]
+    fn hardcoded_tables_are_derived() {
+        assert_eq!(sha_256_h_0(), math::H_0);
+        assert_eq!(sha_224_h_0(), math::H_0_224);
+        assert_eq!(sha_256_k(), math::K);
+    }
+
+    #[test]
+    fn sha_512_h_0_test() {
+        let h_0_good = [
+            0x6a09e667f3bcc908,
+            0xbb67ae8584caa73b,
+            0x3c6ef372fe94f82b,
+            0xa54ff53a5f1d36f1,
+            0x510e527fade682d1,
+            0x9b05688c2b3e6c1f,
+            0x1f83d9abfb41bd6b,
+            0x5be0cd19137e2179,
+        ];
+
+        assert_eq!(sha_512_h_0(), h_0_good);
+
+        // SHA-256 H(0) is the high half of SHA-512's
+        for (h_512, h_256) in sha_512_h_0().into_iter().zip(math::H_0) {
+            assert_eq!((h_512 >> 32) as u32, h_256);
+        }
+    }
+}
@@ -0,0 +1,93 @@
+//!    This module holds the constant-time helpers used to compare digests and to parse them:
+//!    their running time depends on the length of their inputs, never on their content, so
+//!    comparing a computed tag with an expected one doesn't tell how many bytes matched.
+
+use std::hint::black_box;
+
+/// Whether `a` and `b` are equal, looking at every byte even after a difference
+pub(crate) fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    // lengths are public, only the content has to be protected
+    if a.len() != b.len() {
+        return false;
+    }
+
+    let diff = a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y));
+
+    // black_box keeps the compiler from turning the fold into an early-exit comparison
+    black_box(diff) == 0
+}
+
+/// Value of the hex digit `c` and a mask that is 0xff when `c` is a hex digit, 0 otherwise,
+/// computed with arithmetic only
+fn decode_nibble(c: u8) -> (u8, u8) {
+    let c = c as i16;
+    let lower = c | 0x20; // 'A'..'F' to 'a'..'f'
+
+    // (low - 1 - c) & (c - high - 1) is negative only when low <= c <= high,
+    // so shifting it right by 8 gives -1 (all ones) inside the range and 0 outside
+    let is_digit = ((0x2f - c) & (c - 0x3a)) >> 8;
+    let is_letter = ((0x60 - lower) & (lower - 0x67)) >> 8;
+
+    let value = (is_digit & (c - 0x30)) | (is_letter & (lower - 0x57));
+
+    (value as u8, (is_digit | is_letter) as u8)
+}
+
+/// Decodes `hex` (2 digits per byte, in any case) into `out`, returns whether every digit was valid.
+/// Every digit is decoded whatever the validity of the previous ones.
+pub(crate) fn decode_hex(hex: &[u8], out: &mut [u8]) -> bool {
+    if hex.len() != 2 * out.len() {
+        return false;
+    }
+
+    let mut valid = 0xffu8;
+
+    for (byte, digits) in out.iter_mut().zip(hex.chunks_exact(2)) {
+        let (high, high_valid) = decode_nibble(digits[0]);
+        let (low, low_valid) = decode_nibble(digits[1]);
+
+        *byte = (high << 4) | low;
+        valid &= high_valid & low_valid;
+    }
+
+    black_box(valid) == 0xff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_eq_test() {
+        assert!(bytes_eq(b"", b""));
+        assert!(bytes_eq(b"abc", b"abc"));
+        assert!(!bytes_eq(b"abc", b"abd"));
+        assert!(!bytes_eq(b"abc", b"xbc"));
+        assert!(!bytes_eq(b"abc", b"ab"));
+    }
+
+    #[test]
+    fn nibbles() {
+        for c in 0..=255u8 {
+            let expected = (c as char).to_digit(16);
+            let (value, mask) = decode_nibble(c);
+
+            match expected {
+                Some(digit) => assert_eq!((value, mask), (digit as u8, 0xff), "{c:#x}"),
+                None => assert_eq!(mask, 0, "{c:#x}"),
+            }
+        }
+    }
+
+    #[test]
+    fn decode_hex_test() {
+        let mut out = [0u8; 3];
+
+        assert!(decode_hex(b"00aBfF", &mut out));
+        assert_eq!(out, [0x00, 0xab, 0xff]);
+
+        assert!(!decode_hex(b"00aBfg", &mut out));
+        assert!(!decode_hex(b"+0aBff", &mut out));
+        assert!(!decode_hex(b"00aBf", &mut out));
+    }
+}
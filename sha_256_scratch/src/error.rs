@@ -0,0 +1,68 @@
+//!    This module defines the error returned by the fallible functions of the crate.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Error {
+    /// the message has fewer 512-bits blocks than the requested index
+    BlockOutOfRange {
+        index: usize,
+        nb_blocks: usize,
+    },
+    /// a block must be 64 bytes long, not this length
+    InvalidBlockLength(usize),
+    ZeroPieceSize,
+    /// reduced-round variants have 1 to 64 rounds, not this number
+    InvalidRounds(usize),
+    /// more fractional bits of a root were asked than can be returned
+    UnsupportedPrecision {
+        bits: u32,
+        max: u32,
+    },
+    /// more primes were asked than are computed
+    TooManyPrimes {
+        count: usize,
+        max: usize,
+    },
+    /// the digest doesn't hold the whole hash state (SHA-224), so hashing can't be resumed from it
+    TruncatedDigest,
+    /// a message length doesn't fit in the address space
+    LengthOverflow,
+    /// the hash state is made of 8 words, not this number
+    InvalidStateLength(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::BlockOutOfRange { index, nb_blocks } => write!(
+                f,
+                "block {index} is out of range, the message has {nb_blocks} 512-bits blocks"
+            ),
+            Error::InvalidBlockLength(len) => {
+                write!(f, "a block is 64 bytes long, this one is {len} bytes long")
+            }
+            Error::ZeroPieceSize => f.write_str("the piece size must not be 0"),
+            Error::InvalidRounds(rounds) => write!(
+                f,
+                "the number of rounds must be between 1 and 64, not {rounds}"
+            ),
+            Error::UnsupportedPrecision { bits, max } => write!(
+                f,
+                "{bits} fractional bits were asked, at most {max} are supported"
+            ),
+            Error::TooManyPrimes { count, max } => {
+                write!(f, "{count} primes were asked, at most {max} are computed")
+            }
+            Error::TruncatedDigest => {
+                f.write_str("the digest is truncated, the hash state can't be rebuilt from it")
+            }
+            Error::LengthOverflow => f.write_str("the message length overflows"),
+            Error::InvalidStateLength(len) => {
+                write!(f, "the hash state is made of 8 words, not {len}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,134 @@
+//!    This module shows the length-extension attack on SHA-256, which is why SHA256(secret || msg)
+//!    must not be used as a MAC (HMAC must, see `Hmac`).
+//!
+//!    A SHA-256 digest is the whole hash state after the last padded block. Knowing
+//!    SHA256(secret || msg) and the length of secret || msg, hashing can be resumed from the
+//!    digest words to get SHA256(secret || msg || glue || suffix) without knowing the secret,
+//!    where glue is the padding `pre_process` appended to secret || msg.
+//!    SHA-224 resists it, since its digest is a truncated state.
+
+use crate::error::Error;
+use crate::hasher::{Digest, Sha256};
+pub use crate::padding::glue_padding;
+
+/// Forged message and digest, as accepted by a verifier checking SHA256(secret || msg)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Forgery {
+    pub glue_padding: Vec<u8>,
+    pub msg: Vec<u8>, // known message || glue padding || suffix, without the secret
+    pub digest: Digest,
+}
+
+/// Hash values a..h the digest was made of, `None` for truncated (SHA-224) digests
+pub fn state_from_digest(digest: &Digest) -> Option<[u32; 8]> {
+    let bytes: &[u8; 32] = digest.as_bytes().try_into().ok()?;
+    let mut state = [0u32; 8];
+
+    for (word, chunk) in state.iter_mut().zip(bytes.chunks_exact(4)) {
+        *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+
+    Some(state)
+}
+
+/// SHA256(m || glue || suffix) from the digest of a message m of `msg_len` bytes
+pub fn extend_digest(digest: &Digest, msg_len: usize, suffix: &[u8]) -> Result<Digest, Error> {
+    let state = state_from_digest(digest).ok_or(Error::TruncatedDigest)?;
+    let processed_len = msg_len
+        .checked_add(glue_padding(msg_len as u64).len())
+        .ok_or(Error::LengthOverflow)?;
+
+    let mut hasher = Sha256::from_state(state, processed_len as u64);
+    hasher.update(suffix);
+
+    Ok(hasher.finalize())
+}
+
+/// Appends `suffix` to `known_msg`, whose SHA256(secret || known_msg) is `known_digest`,
+/// knowing only the length of the secret
+pub fn forge(
+    known_digest: &Digest,
+    secret_len: usize,
+    known_msg: &[u8],
+    suffix: &[u8],
+) -> Result<Forgery, Error> {
+    let original_len = secret_len
+        .checked_add(known_msg.len())
+        .ok_or(Error::LengthOverflow)?;
+    let digest = extend_digest(known_digest, original_len, suffix)?;
+    let glue_padding = glue_padding(original_len as u64);
+
+    let mut msg = known_msg.to_vec();
+    msg.extend(&glue_padding);
+    msg.extend(suffix);
+
+    Ok(Forgery {
+        glue_padding,
+        msg,
+        digest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::Sha224;
+
+    fn secret_prefix_mac(secret: &[u8], msg: &[u8]) -> Digest {
+        let mut hasher = Sha256::new();
+        hasher.update(secret);
+        hasher.update(msg);
+
+        hasher.finalize()
+    }
+
+    #[test]
+    fn forged_digest_is_accepted() {
+        let known_msg = b"user=alice&role=user";
+        let suffix = b"&role=admin";
+
+        for secret_len in [0, 1, 16, 35, 36, 44, 64, 100] {
+            let secret = vec![0x5a; secret_len];
+            let known_digest = secret_prefix_mac(&secret, known_msg);
+
+            let forgery = forge(&known_digest, secret_len, known_msg, suffix).unwrap();
+
+            assert!(forgery.msg.starts_with(known_msg));
+            assert!(forgery.msg.ends_with(suffix));
+            assert_eq!(forgery.digest, secret_prefix_mac(&secret, &forgery.msg));
+        }
+    }
+
+    #[test]
+    fn wrong_secret_length_fails() {
+        let secret = b"0123456789abcdef";
+        let known_digest = secret_prefix_mac(secret, b"msg");
+
+        let forgery = forge(&known_digest, secret.len() + 1, b"msg", b"suffix").unwrap();
+
+        assert_ne!(forgery.digest, secret_prefix_mac(secret, &forgery.msg));
+    }
+
+    #[test]
+    fn truncated_digest_cant_be_extended() {
+        let mut hasher = Sha224::new();
+        hasher.update(b"secret and msg");
+        let digest = hasher.finalize();
+
+        assert_eq!(state_from_digest(&digest), None);
+        assert_eq!(
+            forge(&digest, 6, b"and msg", b"suffix"),
+            Err(Error::TruncatedDigest)
+        );
+    }
+
+    #[test]
+    fn length_overflow() {
+        let digest = secret_prefix_mac(b"secret", b"msg");
+
+        assert_eq!(
+            forge(&digest, usize::MAX, b"msg", b"suffix"),
+            Err(Error::LengthOverflow)
+        );
+    }
+}
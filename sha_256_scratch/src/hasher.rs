@@ -0,0 +1,452 @@
+//!    This module defines incremental SHA-256, SHA-224 and double SHA-256 hashers.
+//!    Contrary to `sha_256`, the message doesn't need to be fully loaded in memory:
+//!    it is given piece by piece to `update` and only one 512-bits block is buffered at a time.
+
+use std::fmt;
+use std::hash::{Hash, Hasher};
+
+use crate::ct;
+use crate::math;
+use crate::wipe::wipe;
+use crate::working_variables::WorkingVariables;
+
+/// Incremental SHA-256 hasher.
+/// Its buffered block and hash values are wiped on drop and by `reset`, since they may come
+/// from a key (HMAC, or a secret prefix).
+#[derive(Clone)]
+pub struct Sha256 {
+    hash: [u32; 8],
+    block: [u8; 64],
+    block_len: usize,
+    msg_len: u64, // in bytes
+}
+
+impl Sha256 {
+    pub fn new() -> Sha256 {
+        Self::with_initial_hash(math::H_0)
+    }
+
+    fn with_initial_hash(hash: [u32; 8]) -> Sha256 {
+        Self::from_state(hash, 0)
+    }
+
+    /// Hasher resuming from the hash values reached after `msg_len` bytes, a multiple of 64
+    pub(crate) fn from_state(hash: [u32; 8], msg_len: u64) -> Sha256 {
+        Self {
+            hash,
+            block: [0; 64],
+            block_len: 0,
+            msg_len,
+        }
+    }
+
+    /// Feeds the hasher with the next part of the message
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.msg_len = self.msg_len.wrapping_add(data.len() as u64);
+
+        // completing the block started by a previous call
+        if self.block_len > 0 {
+            let taken = data.len().min(64 - self.block_len);
+
+            self.block[self.block_len..self.block_len + taken].copy_from_slice(&data[..taken]);
+            self.block_len += taken;
+            data = &data[taken..];
+
+            if self.block_len < 64 {
+                return;
+            }
+
+            let mut block = self.block;
+            self.compress_block(&block);
+            self.block_len = 0;
+
+            wipe(&mut block);
+        }
+
+        // compressing full blocks directly from the input
+        let mut blocks = data.chunks_exact(64);
+
+        for block in &mut blocks {
+            self.compress_block(block);
+        }
+
+        let remainder = blocks.remainder();
+        self.block[..remainder.len()].copy_from_slice(remainder);
+        self.block_len = remainder.len();
+    }
+
+    /// Pads the message as `pre_process` does and returns the final hash
+    pub fn finalize(mut self) -> Digest {
+        let msg_len = self.msg_len;
+        let padding = crate::padding::glue_padding(msg_len);
+
+        // the length has already been computed, so update() mustn't count the padding in it
+        self.update(&padding);
+        self.msg_len = msg_len;
+
+        Digest::from_hash(self.hash)
+    }
+
+    fn compress_block(&mut self, block: &[u8]) {
+        let working_var = WorkingVariables::from_array(&self.hash);
+        let schedule = crate::message_schedule(block);
+
+        let mut compressed = crate::compress_chunk(working_var, schedule, &math::K);
+        let mut updated = crate::add_compressed_chunk_in_hash(&self.hash, &compressed);
+
+        // the round state and its sum depend on the block, which may hold a key
+        self.hash = updated;
+        wipe(&mut updated);
+        compressed.wipe();
+    }
+
+    /// Wipes the buffered data and starts a new message, as `new` would
+    pub fn reset(&mut self) {
+        self.reset_to(math::H_0);
+    }
+
+    fn reset_to(&mut self, hash: [u32; 8]) {
+        wipe(&mut self.block);
+        self.hash = hash;
+        self.block_len = 0;
+        self.msg_len = 0;
+    }
+}
+
+impl Drop for Sha256 {
+    fn drop(&mut self) {
+        wipe(&mut self.hash);
+        wipe(&mut self.block);
+        self.block_len = 0;
+        self.msg_len = 0;
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Incremental SHA-224 hasher: SHA-256 with another initial hash, truncated to 224 bits
+pub struct Sha224(Sha256);
+
+impl Sha224 {
+    pub fn new() -> Sha224 {
+        Self(Sha256::with_initial_hash(math::H_0_224))
+    }
+
+    /// Feeds the hasher with the next part of the message
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// Wipes the buffered data and starts a new message, as `new` would
+    pub fn reset(&mut self) {
+        self.0.reset_to(math::H_0_224);
+    }
+
+    pub fn finalize(self) -> Digest {
+        let mut digest = self.0.finalize();
+
+        digest.bytes[28..].fill(0);
+        digest.len = 28;
+
+        digest
+    }
+}
+
+impl Default for Sha224 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Incremental double SHA-256 hasher: SHA256(SHA256(m)), as used for Bitcoin block and transaction IDs.
+/// Bitcoin displays these IDs with their bytes reversed.
+pub struct Sha256d(Sha256);
+
+impl Sha256d {
+    pub fn new() -> Sha256d {
+        Self(Sha256::new())
+    }
+
+    /// Feeds the hasher with the next part of the message
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// Wipes the buffered data and starts a new message, as `new` would
+    pub fn reset(&mut self) {
+        self.0.reset();
+    }
+
+    pub fn finalize(self) -> Digest {
+        let mut outer = Sha256::new();
+        outer.update(self.0.finalize().as_bytes());
+
+        outer.finalize()
+    }
+}
+
+impl Default for Sha256d {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Hash value computed by `Sha256` and `Sha256d` (32 bytes) or `Sha224` (28 bytes).
+/// `==` compares digests in constant time, like `ct_eq`.
+#[derive(Debug, Clone, Copy)]
+pub struct Digest {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl Digest {
+    /// SHA-256 digest made of the final hash values
+    pub(crate) fn from_hash(hash: [u32; 8]) -> Digest {
+        let mut bytes = [0u8; 32];
+
+        for (chunk, word) in bytes.chunks_exact_mut(4).zip(hash) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+
+        Digest { bytes, len: 32 }
+    }
+
+    /// Parses a SHA-256 (64 hex digits) or SHA-224 (56 hex digits) digest, in any case.
+    /// The digits are decoded without branching on their value, so expected tags can be parsed safely.
+    pub fn from_hex(hex: &str) -> Option<Digest> {
+        if hex.len() != 64 && hex.len() != 56 {
+            return None;
+        }
+
+        let len = hex.len() / 2;
+        let mut bytes = [0u8; 32];
+
+        ct::decode_hex(hex.as_bytes(), &mut bytes[..len]).then_some(Digest { bytes, len })
+    }
+
+    /// Overwrites the digest with zeros, for digests used as keys
+    pub(crate) fn wipe(&mut self) {
+        wipe(&mut self.bytes);
+    }
+
+    /// Whether both digests are equal, in a time that doesn't depend on where they differ
+    pub fn ct_eq(&self, other: &Digest) -> bool {
+        ct::bytes_eq(self.as_bytes(), other.as_bytes())
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+
+    /// Lowercase hexadecimal representation, as returned by `sha_256`
+    pub fn to_hex(&self) -> String {
+        self.as_bytes()
+            .iter()
+            .fold(String::new(), |hex, byte| format!("{}{:02x}", hex, byte))
+    }
+}
+
+impl PartialEq for Digest {
+    fn eq(&self, other: &Digest) -> bool {
+        self.ct_eq(other)
+    }
+}
+
+impl Eq for Digest {}
+
+impl Hash for Digest {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_bytes().hash(state);
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn hash_in_pieces(msg: &[u8], piece_len: usize) -> Digest {
+        let mut hasher = Sha256::new();
+
+        for piece in msg.chunks(piece_len) {
+            hasher.update(piece);
+        }
+
+        hasher.finalize()
+    }
+
+    #[test]
+    fn empty_message() {
+        let hash = Sha256::new().finalize();
+
+        assert_eq!(
+            hash.to_hex(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn same_as_sha_256_whatever_the_pieces_length() {
+        let msg = fs::read("./sample_files_for_testing/sample.pdf").unwrap();
+        let hash_good = crate::sha_256(msg.clone());
+
+        for piece_len in [1, 3, 55, 56, 63, 64, 65, 128, 1000, msg.len()] {
+            assert_eq!(hash_in_pieces(&msg, piece_len).to_hex(), hash_good);
+        }
+    }
+
+    #[test]
+    fn padding_boundaries() {
+        // 55 bytes: length fits in the same block, 56 bytes: it needs a new one
+        for msg_len in 54..=66 {
+            let msg = vec![b'a'; msg_len];
+
+            assert_eq!(
+                hash_in_pieces(&msg, 7).to_hex(),
+                crate::sha_256(msg.clone())
+            );
+        }
+    }
+
+    #[test]
+    fn sha_224_test() {
+        let empty = Sha224::new().finalize();
+
+        assert_eq!(
+            empty.to_hex(),
+            "d14a028c2a3a2bc9476102bb288234c415a2b01f828ea62ac5b3e42f"
+        );
+
+        let mut hasher = Sha224::new();
+        hasher.update(b"abc");
+
+        assert_eq!(
+            hasher.finalize().to_hex(),
+            "23097d223405d8228642a477bda255b32aadbce4bda0b3f7e36c9da7"
+        );
+
+        let mut hasher = Sha224::new();
+        hasher.update(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq");
+
+        assert_eq!(
+            hasher.finalize().to_hex(),
+            "75388b16512776cc5dba5da1fd890150b0c6455cb4f58b1952522525"
+        );
+    }
+
+    #[test]
+    fn from_hex_test() {
+        let hex = "8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4";
+        let digest = Digest::from_hex(&hex.to_uppercase()).unwrap();
+        assert_eq!(digest.to_hex(), hex);
+
+        let hex_224 = "1a15bca3e4ed3ab82367c6c434f86c41040bdac577fa1c2de62c3be0";
+        assert_eq!(Digest::from_hex(hex_224).unwrap().as_bytes().len(), 28);
+
+        assert!(Digest::from_hex("").is_none());
+        assert!(Digest::from_hex(&hex[1..]).is_none());
+        assert!(Digest::from_hex(&hex.replace('f', "g")).is_none());
+        assert!(Digest::from_hex(&format!("+f{}", &hex[2..])).is_none());
+    }
+
+    #[test]
+    fn reset_wipes_buffered_data() {
+        let mut hasher = Sha256::new();
+        hasher.update(&[0x5a; 100]);
+
+        hasher.reset();
+        assert_eq!(hasher.block, [0; 64]);
+        assert_eq!((hasher.block_len, hasher.msg_len), (0, 0));
+
+        hasher.update(b"abc");
+        assert_eq!(hasher.finalize(), hash_in_pieces(b"abc", 3));
+
+        let mut hasher = Sha224::new();
+        hasher.update(b"secret");
+        hasher.reset();
+        hasher.update(b"abc");
+        assert_eq!(hasher.finalize().to_hex(), crate::sha_224(b"abc".to_vec()));
+
+        let mut hasher = Sha256d::new();
+        hasher.update(b"secret");
+        hasher.reset();
+        hasher.update(b"abc");
+        assert_eq!(hasher.finalize().to_hex(), crate::sha_256d(b"abc".to_vec()));
+    }
+
+    #[test]
+    fn ct_eq_test() {
+        let digest = hash_in_pieces(b"abc", 1);
+        let hex = digest.to_hex();
+
+        assert!(digest.ct_eq(&Digest::from_hex(&hex).unwrap()));
+        assert_eq!(digest, Digest::from_hex(&hex.to_uppercase()).unwrap());
+
+        let last_byte_differs = format!("{}00", &hex[..62]);
+        assert!(!digest.ct_eq(&Digest::from_hex(&last_byte_differs).unwrap()));
+
+        // a SHA-224 digest never equals the SHA-256 digest it would be a prefix of
+        let prefix = Digest::from_hex(&hex[..56]).unwrap();
+        assert!(!digest.ct_eq(&prefix));
+        assert_ne!(digest, prefix);
+    }
+
+    #[test]
+    fn display_is_hex() {
+        let mut hasher = Sha256::new();
+        hasher.update(b"hi");
+
+        assert_eq!(
+            format!("{}", hasher.finalize()),
+            "8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4"
+        );
+    }
+
+    #[test]
+    fn sha_256d_bitcoin_block_ids() {
+        // (header, block ID) of the genesis block and of block 1
+        let blocks = [
+            (
+                concat!(
+                    "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b2",
+                    "7ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4a29ab5f49ffff001d1dac2b7c"
+                ),
+                "000000000019d6689c085ae165831e934ff763ae46a2a6c172b3f1b60a8ce26f",
+            ),
+            (
+                concat!(
+                    "010000006fe28c0ab6f1b372c1a6a246ae63f74f931e8365e15a089c68d6190000000000982051fd1e4ba744",
+                    "bbbe680e1fee14677ba1a3c3540bf7b1cdb606e857233e0e61bc6649ffff001d01e36299"
+                ),
+                "00000000839a8e6886ab5951d76f411475428afc90947ee320161bbf18eb6048",
+            ),
+        ];
+
+        for (header, id) in blocks {
+            let header: Vec<u8> = (0..header.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&header[i..i + 2], 16).unwrap())
+                .collect();
+
+            let mut hasher = Sha256d::new();
+            hasher.update(&header[..40]);
+            hasher.update(&header[40..]);
+
+            let mut digest = hasher.finalize().as_bytes().to_vec();
+            digest.reverse();
+
+            let id_found: String = digest.iter().map(|byte| format!("{byte:02x}")).collect();
+            assert_eq!(id_found, id);
+        }
+    }
+}
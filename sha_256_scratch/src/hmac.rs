@@ -0,0 +1,159 @@
+//!    This module defines HMAC-SHA256 (RFC 2104), the keyed hash used to authenticate messages:
+//!    HMAC(K, m) = SHA256((K' ^ opad) || SHA256((K' ^ ipad) || m))
+//!    where K' is the key hashed if longer than a block, then padded with zeros to a block.
+
+use crate::hasher::{Digest, Sha256};
+use crate::wipe::wipe;
+
+const BLOCK_LEN: usize = 64; // in bytes
+const INNER_PAD: u8 = 0x36;
+const OUTER_PAD: u8 = 0x5c;
+
+/// Key XORed with both pads, wiped on drop
+struct KeyPads {
+    inner: [u8; BLOCK_LEN],
+    outer: [u8; BLOCK_LEN],
+}
+
+impl Drop for KeyPads {
+    fn drop(&mut self) {
+        wipe(&mut self.inner);
+        wipe(&mut self.outer);
+    }
+}
+
+/// Incremental HMAC-SHA256.
+/// The key pads and the inner hasher are wiped on drop, the inner hash once finalized.
+pub struct Hmac {
+    inner: Sha256,
+    key_pads: KeyPads,
+}
+
+impl Hmac {
+    pub fn new(key: &[u8]) -> Hmac {
+        let mut block_key = [0u8; BLOCK_LEN];
+
+        if key.len() > BLOCK_LEN {
+            let mut hasher = Sha256::new();
+            hasher.update(key);
+
+            let mut hashed_key = hasher.finalize();
+            block_key[..hashed_key.as_bytes().len()].copy_from_slice(hashed_key.as_bytes());
+            hashed_key.wipe();
+        } else {
+            block_key[..key.len()].copy_from_slice(key);
+        }
+
+        let mut key_pads = KeyPads {
+            inner: [0u8; BLOCK_LEN],
+            outer: [0u8; BLOCK_LEN],
+        };
+
+        for (i, byte) in block_key.iter().enumerate() {
+            key_pads.inner[i] = byte ^ INNER_PAD;
+            key_pads.outer[i] = byte ^ OUTER_PAD;
+        }
+
+        wipe(&mut block_key);
+
+        let mut inner = Sha256::new();
+        inner.update(&key_pads.inner);
+
+        Self { inner, key_pads }
+    }
+
+    /// Feeds the HMAC with the next part of the message
+    pub fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+
+    /// Wipes the message buffered so far and starts a new one with the same key
+    pub fn reset(&mut self) {
+        self.inner.reset();
+        self.inner.update(&self.key_pads.inner);
+    }
+
+    pub fn finalize(self) -> Digest {
+        let mut inner_hash = self.inner.finalize();
+
+        let mut outer = Sha256::new();
+        outer.update(&self.key_pads.outer);
+        outer.update(inner_hash.as_bytes());
+
+        // the inner hash is keyed, only the outer one is meant to be disclosed
+        inner_hash.wipe();
+
+        outer.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hmac(key: &[u8], msg: &[u8]) -> String {
+        let mut hmac = Hmac::new(key);
+        hmac.update(msg);
+
+        hmac.finalize().to_hex()
+    }
+
+    // test cases from RFC 4231
+    #[test]
+    fn rfc_4231_short_key() {
+        assert_eq!(
+            hmac(&[0x0b; 20], b"Hi There"),
+            "b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7"
+        );
+
+        assert_eq!(
+            hmac(b"Jefe", b"what do ya want for nothing?"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn rfc_4231_block_sized_data() {
+        assert_eq!(
+            hmac(&[0xaa; 20], &[0xdd; 50]),
+            "773ea91e36800e46854db8ebd09181a72959098b3ef8c122d9635514ced565fe"
+        );
+    }
+
+    #[test]
+    fn rfc_4231_key_longer_than_block() {
+        assert_eq!(
+            hmac(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ),
+            "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"
+        );
+    }
+
+    #[test]
+    fn message_given_in_pieces() {
+        let mut hmac = Hmac::new(b"Jefe");
+        hmac.update(b"what do ya want ");
+        hmac.update(b"for nothing?");
+
+        assert_eq!(
+            hmac.finalize().to_hex(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn reset_keeps_the_key() {
+        let mut hmac = Hmac::new(b"Jefe");
+        hmac.update(b"some other message");
+
+        hmac.reset();
+        hmac.update(b"what do ya want for nothing?");
+
+        assert_eq!(
+            hmac.finalize().to_hex(),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+}
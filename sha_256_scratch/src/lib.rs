@@ -1,37 +1,45 @@
 #![forbid(unsafe_code)]
 
+#[cfg(feature = "async")]
+mod async_reader;
+pub mod constants;
+mod ct;
+mod error;
+pub mod extension;
+mod hasher;
+mod hmac;
 mod math;
+pub mod merkle;
+pub mod padding;
+mod pieces;
+#[cfg(feature = "reduced-rounds")]
+pub mod reduced;
+mod tagged;
+pub mod trace;
+mod wipe;
 mod working_variables;
-use working_variables::WorkingVariables;
+#[cfg(feature = "async")]
+pub use async_reader::{hash_async_reader, AsyncHashingReader};
+pub use error::Error;
+pub use hasher::{Digest, Sha224, Sha256, Sha256d};
+pub use hmac::Hmac;
+pub use pieces::{piece_hashes, PieceHasher, Pieces};
+pub use tagged::TaggedHasher;
+pub use wipe::wipe;
+pub use working_variables::WorkingVariables;
 
 fn pre_process(mut msg: Vec<u8>) -> Vec<u8> {
-    let original_length_bits = (msg.len() * 8) as u64;
-
-    // appending 1 (1000 0000)
-    msg.push(128);
-
-    // padding with zeros such as msg length is a multiple of 512
-    let nb_zero_bits = (512 + 448 - ((original_length_bits as u32) % 512 + 1)) % 512;
-    let nb_zero_bytes = nb_zero_bits / 8;
-
-    msg.append(&mut vec![0; nb_zero_bytes as usize]);
-
-    // padding original length as 64 bits
-    let mut mask = 0xFF00000000000000;
-
-    for i in 0..8 {
-        let val64 = original_length_bits & mask;
-        let val = (val64 >> (56 - (8 * i))) as u8;
-        msg.push(val);
-        mask >>= 8;
-    }
+    // 1 bit, zeros up to 448 bits in the last block, then the length as 64 bits
+    msg.extend(padding::glue_padding(msg.len() as u64));
 
     msg
 }
 
-fn parse_block(msg: &[u8], index: usize) -> Result<&[u8], &'static str> {
-    if index > msg.len() / 64 {
-        return Err("index is greater than the number of 512-bits blocks");
+fn parse_block(msg: &[u8], index: usize) -> Result<&[u8], Error> {
+    let nb_blocks = msg.len() / 64;
+
+    if index >= nb_blocks {
+        return Err(Error::BlockOutOfRange { index, nb_blocks });
     }
 
     let start = (512 * index) / 8;
@@ -69,7 +77,12 @@ fn message_schedule(chunk: &[u8]) -> Vec<u32> {
     w
 }
 
-fn compress_word(current: WorkingVariables, word: u32, k: u32) -> WorkingVariables {
+fn compress_word(current: &mut WorkingVariables, word: u32, k: u32) {
+    compress_round(current, word, k);
+}
+
+/// One round of the compression function, in place, returning its two temporary words T1 and T2
+fn compress_round(current: &mut WorkingVariables, word: u32, k: u32) -> (u32, u32) {
     let s1 = math::big_sigma_1(current.e);
     let ch = math::choice(current.e, current.f, current.g);
     let temp1 = current
@@ -83,43 +96,47 @@ fn compress_word(current: WorkingVariables, word: u32, k: u32) -> WorkingVariabl
     let maj = math::majority(current.a, current.b, current.c);
     let temp2 = s0.wrapping_add(maj);
 
-    let h = current.g;
-    let g = current.f;
-    let f = current.e;
-    let e = current.d.wrapping_add(temp1);
-    let d = current.c;
-    let c = current.b;
-    let b = current.a;
-    let a = temp1.wrapping_add(temp2);
+    current.h = current.g;
+    current.g = current.f;
+    current.f = current.e;
+    current.e = current.d.wrapping_add(temp1);
+    current.d = current.c;
+    current.c = current.b;
+    current.b = current.a;
+    current.a = temp1.wrapping_add(temp2);
 
-    WorkingVariables::new(&[a, b, c, d, e, f, g, h])
+    (temp1, temp2)
 }
 
+/// Runs one round per constant of `k`: the 64 rounds of SHA-256 with `math::K`
 fn compress_chunk(
     init_working_var: WorkingVariables,
-    schedule: Vec<u32>,
+    mut schedule: Vec<u32>,
     k: &[u32],
 ) -> WorkingVariables {
     let mut current_working_var = init_working_var;
 
-    for i in 0..64 {
-        current_working_var = compress_word(current_working_var, schedule[i], k[i]);
+    for (word, k) in schedule.iter().zip(k) {
+        compress_word(&mut current_working_var, *word, *k);
     }
 
+    // the schedule is derived from the block, which may hold a key
+    wipe::wipe(&mut schedule);
+
     current_working_var
 }
 
-fn add_compressed_chunk_in_hash(hash: &[u32], compressed: &WorkingVariables) -> Vec<u32> {
-    let mut updated: Vec<u32> = Vec::new();
+fn add_compressed_chunk_in_hash(hash: &[u32; 8], compressed: &WorkingVariables) -> [u32; 8] {
+    let mut updated = [0u32; 8];
 
-    for (index, var) in compressed.iter().enumerate() {
-        updated.push(hash[index].wrapping_add(*var));
+    for ((updated, hash), var) in updated.iter_mut().zip(hash).zip(compressed.iter()) {
+        *updated = hash.wrapping_add(*var);
     }
 
     updated
 }
 
-fn append_hash_values(hash_values: Vec<u32>) -> String {
+fn append_hash_values(hash_values: [u32; 8]) -> String {
     hash_values
         .into_iter()
         .fold(String::new(), |full_hash, hash| {
@@ -131,18 +148,14 @@ fn compress_msg(
     msg: Vec<u8>,
     init_working_var: WorkingVariables,
     k: &[u32],
-    init_hash: &[u32],
-) -> Vec<u32> {
-    let mut hash = init_hash.to_vec();
+    init_hash: &[u32; 8],
+) -> [u32; 8] {
+    let mut hash = *init_hash;
     let mut working_var = init_working_var;
 
-    for i in 0..msg.len() / 64 {
-        working_var.update(&hash);
-
-        let block = match parse_block(&msg, i) {
-            Ok(block) => block,
-            Err(err) => panic!("{err}"),
-        };
+    // blocks are parsed until the index goes past the last one
+    for block in (0..).map_while(|i| parse_block(&msg, i).ok()) {
+        working_var.update_from_array(&hash);
 
         let schedule = message_schedule(block);
 
@@ -151,6 +164,8 @@ fn compress_msg(
         hash = add_compressed_chunk_in_hash(&hash, &working_var);
     }
 
+    working_var.wipe();
+
     hash
 }
 
@@ -158,13 +173,43 @@ pub fn sha_256(raw_msg: Vec<u8>) -> String {
     let msg = pre_process(raw_msg);
 
     let (hash, k) = (math::H_0, math::K);
-    let init_working_var = WorkingVariables::new(&hash);
+    let init_working_var = WorkingVariables::from_array(&hash);
 
     let updated_hash = compress_msg(msg, init_working_var, &k, &hash);
 
     append_hash_values(updated_hash)
 }
 
+/// Whether `expected` is the SHA-256 digest of `data`, compared in constant time
+pub fn verify(data: &[u8], expected: &[u8; 32]) -> bool {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+
+    ct::bytes_eq(hasher.finalize().as_bytes(), expected)
+}
+
+pub fn sha_224(raw_msg: Vec<u8>) -> String {
+    let mut hasher = Sha224::new();
+    hasher.update(&raw_msg);
+
+    hasher.finalize().to_hex()
+}
+
+/// Double SHA-256, the digest bytes in their hash order (Bitcoin displays IDs reversed)
+pub fn sha_256d(raw_msg: Vec<u8>) -> String {
+    let mut hasher = Sha256d::new();
+    hasher.update(&raw_msg);
+
+    hasher.finalize().to_hex()
+}
+
+pub fn hmac_sha_256(key: &[u8], raw_msg: Vec<u8>) -> String {
+    let mut hmac = Hmac::new(key);
+    hmac.update(&raw_msg);
+
+    hmac.finalize().to_hex()
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -226,7 +271,7 @@ mod tests {
         let schedule = get_schedule_short();
         let (hash, k) = (math::H_0, math::K);
 
-        let init_working_var = WorkingVariables::new(&hash);
+        let init_working_var = WorkingVariables::from_array(&hash);
 
         compress_chunk(init_working_var, schedule, &k)
     }
@@ -287,14 +332,18 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "index is greater than the number of 512-bits blocks")]
     fn parse_block_invalid() {
         let msg = get_short_pre_processed(); // setting up this scenario
 
-        let _block = match parse_block(&msg, 65) {
-            Ok(block) => block,
-            Err(err) => panic!("{err}"),
-        };
+        for index in [1, 65] {
+            assert_eq!(
+                parse_block(&msg, index),
+                Err(Error::BlockOutOfRange {
+                    index,
+                    nb_blocks: 1
+                })
+            );
+        }
     }
 
     #[test]
@@ -375,11 +424,11 @@ mod tests {
         let schedule = get_schedule_short(); // setting up this scenario
 
         let (current, k) = (math::H_0, math::K);
-        let current_working_var = WorkingVariables::new(&current);
+        let mut compressed = WorkingVariables::from_array(&current);
 
-        let compressed = compress_word(current_working_var, schedule[0], k[0]);
+        compress_word(&mut compressed, schedule[0], k[0]);
 
-        let compressed_good = WorkingVariables::new(&[
+        let compressed_good = WorkingVariables::from_array(&[
             0x6472084d, 0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0x13162a2, 0x510e527f, 0x9b05688c,
             0x1f83d9ab,
         ]);
@@ -393,10 +442,10 @@ mod tests {
 
         let (hash, k) = (math::H_0, math::K);
 
-        let init_working_var = WorkingVariables::new(&hash);
+        let init_working_var = WorkingVariables::from_array(&hash);
         let compressed = compress_chunk(init_working_var, schedule, &k);
 
-        let compressed_good = WorkingVariables::new(&[
+        let compressed_good = WorkingVariables::from_array(&[
             0x25395cdf, 0xa927bd11, 0xa31aea37, 0x5c752231, 0xbf9885ba, 0xc6d7d38e, 0xa9078007,
             0x8051ad8b,
         ]);
@@ -410,11 +459,11 @@ mod tests {
 
         let (hash, k) = (math::H_0, math::K);
 
-        let init_working_var = WorkingVariables::new(&hash);
+        let init_working_var = WorkingVariables::from_array(&hash);
 
         let updated_hash = compress_msg(msg, init_working_var, &k, &hash);
 
-        let updated_hash_good = vec![
+        let updated_hash_good = [
             0x3e24531c, 0xdaa595ab, 0x56f976b9, 0x6c1a1df8, 0x009eabec, 0x300a5a02, 0x61c0e44f,
             0x47a43b89,
         ];
@@ -429,7 +478,7 @@ mod tests {
 
         let updated_hash = add_compressed_chunk_in_hash(&hash, &compressed);
 
-        let updated = vec![
+        let updated = [
             0x8f434346, 0x648f6b96, 0xdf89dda9, 0x01c5176b, 0x10a6d839, 0x61dd3c1a, 0xc88b59b2,
             0xdc327aa4,
         ];
@@ -439,7 +488,7 @@ mod tests {
 
     #[test]
     fn append_hash_values_test() {
-        let updated_hash = vec![
+        let updated_hash = [
             0x8f434346, 0x648f6b96, 0xdf89dda9, 0x01c5176b, 0x10a6d839, 0x61dd3c1a, 0xc88b59b2,
             0xdc327aa4,
         ];
@@ -482,6 +531,26 @@ mod tests {
         assert_eq!(hash, hash_good);
     }
 
+    #[test]
+    fn sha_224_one_chunk() {
+        let msg = String::from("hi").as_bytes().to_vec();
+
+        let hash = sha_224(msg);
+        let hash_good = "1a15bca3e4ed3ab82367c6c434f86c41040bdac577fa1c2de62c3be0";
+
+        assert_eq!(hash, hash_good);
+    }
+
+    #[test]
+    fn sha_256d_one_chunk() {
+        let msg = String::from("hello").as_bytes().to_vec();
+
+        let hash = sha_256d(msg);
+        let hash_good = "9595c9df90075148eb06860365df33584b75bff782a510c6cd4883a419833d50";
+
+        assert_eq!(hash, hash_good);
+    }
+
     #[test]
     fn sha_256_file() {
         let msg = fs::read("./sample_files_for_testing/sample").unwrap();
@@ -501,4 +570,20 @@ mod tests {
 
         assert_eq!(hash, hash_good);
     }
+
+    #[test]
+    fn verify_test() {
+        let mut expected = [0u8; 32];
+        let hex = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+
+        for (byte, i) in expected.iter_mut().zip((0..64).step_by(2)) {
+            *byte = u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+        }
+
+        assert!(verify(b"abc", &expected));
+        assert!(!verify(b"abd", &expected));
+
+        expected[31] ^= 1;
+        assert!(!verify(b"abc", &expected));
+    }
 }
@@ -1,9 +1,15 @@
 // first thirty-two bits of the fractional parts of the square roots of the first eight prime numbers
-// set by the SHA-256 specification
+// set by the SHA-256 specification (derived in `constants`, as the other tables)
 pub const H_0: [u32; 8] = [
     0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
 ];
 
+// second thirty-two bits of the fractional parts of the square roots of the 9th through 16th prime numbers
+// set by the SHA-224 specification
+pub const H_0_224: [u32; 8] = [
+    0xc1059ed8, 0x367cd507, 0x3070dd17, 0xf70e5939, 0xffc00b31, 0x68581511, 0x64f98fa7, 0xbefa4fa4,
+];
+
 //first thirty-two bits of the fractional parts of the cube roots of the first sixty-four prime numbers
 // set by the SHA-256 specification
 pub const K: [u32; 64] = [
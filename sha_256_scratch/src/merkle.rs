@@ -0,0 +1,545 @@
+//!    This module defines the Merkle tree hashing of RFC 6962 (Certificate Transparency), as used by
+//!    append-only logs. Leaves and interior nodes are hashed with distinct prefixes, so a leaf
+//!    can never be passed off as a node:
+//!    leaf hash = SHA256(0x00 || data), node hash = SHA256(0x01 || left || right)
+//!
+//!    The tree of n leaves splits them at k, the largest power of two smaller than n, so the
+//!    tree head of the first leaves never changes when leaves are appended. The head of an empty tree
+//!    is SHA256 of the empty string.
+//!
+//!    Inclusion proofs (audit paths) show a leaf is in a tree of a given size, consistency proofs
+//!    show a tree is an append-only extension of an older one. They are generated as specified
+//!    by RFC 6962 section 2.1 and verified with the algorithms of RFC 9162 section 2.1.
+
+use crate::hasher::{Digest, Sha256};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+pub fn leaf_hash(data: &[u8]) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(&[LEAF_PREFIX]);
+    hasher.update(data);
+
+    hasher.finalize()
+}
+
+pub fn node_hash(left: &Digest, right: &Digest) -> Digest {
+    let mut hasher = Sha256::new();
+    hasher.update(&[NODE_PREFIX]);
+    hasher.update(left.as_bytes());
+    hasher.update(right.as_bytes());
+
+    hasher.finalize()
+}
+
+/// Append-only Merkle tree, keeping the hashes of its leaves
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MerkleTree {
+    leaves: Vec<Digest>,
+}
+
+impl MerkleTree {
+    pub fn new() -> MerkleTree {
+        MerkleTree { leaves: Vec::new() }
+    }
+
+    /// Appends a leaf given its data, returns its index
+    pub fn push(&mut self, data: &[u8]) -> usize {
+        self.push_leaf_hash(leaf_hash(data))
+    }
+
+    /// Appends a leaf given its leaf hash, returns its index
+    pub fn push_leaf_hash(&mut self, leaf_hash: Digest) -> usize {
+        self.leaves.push(leaf_hash);
+
+        self.leaves.len() - 1
+    }
+
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    pub fn leaf_hash(&self, index: usize) -> Option<Digest> {
+        self.leaves.get(index).copied()
+    }
+
+    /// Tree head of all the leaves
+    pub fn root(&self) -> Digest {
+        tree_head(&self.leaves)
+    }
+
+    /// Tree head of the first `size` leaves
+    pub fn root_at(&self, size: usize) -> Option<Digest> {
+        Some(tree_head(self.leaves.get(..size)?))
+    }
+
+    /// Audit path of the leaf at `index` in the tree of the first `size` leaves
+    pub fn inclusion_proof(&self, index: usize, size: usize) -> Option<Vec<Digest>> {
+        if index >= size {
+            return None;
+        }
+
+        let mut proof = Vec::new();
+        audit_path(index, self.leaves.get(..size)?, &mut proof);
+
+        Some(proof)
+    }
+
+    /// Proof that the tree of the first `old_size` leaves is a prefix of the one of the first `new_size`
+    pub fn consistency_proof(&self, old_size: usize, new_size: usize) -> Option<Vec<Digest>> {
+        if old_size > new_size {
+            return None;
+        }
+
+        let leaves = self.leaves.get(..new_size)?;
+        let mut proof = Vec::new();
+
+        // the empty tree is a prefix of any tree, and a tree is a prefix of itself
+        if 0 < old_size && old_size < new_size {
+            subproof(old_size, leaves, true, &mut proof);
+        }
+
+        Some(proof)
+    }
+}
+
+/// Checks that `leaf_hash` is the leaf at `index` in the tree of `size` leaves whose head is `root`
+pub fn verify_inclusion(
+    leaf_hash: &Digest,
+    index: u64,
+    size: u64,
+    proof: &[Digest],
+    root: &Digest,
+) -> bool {
+    if index >= size {
+        return false;
+    }
+
+    let (mut node_index, mut last_index) = (index, size - 1);
+    let mut hash = *leaf_hash;
+
+    for sibling in proof {
+        if last_index == 0 {
+            return false;
+        }
+
+        if node_index & 1 == 1 || node_index == last_index {
+            hash = node_hash(sibling, &hash);
+
+            while node_index & 1 == 0 && node_index != 0 {
+                node_index >>= 1;
+                last_index >>= 1;
+            }
+        } else {
+            hash = node_hash(&hash, sibling);
+        }
+
+        node_index >>= 1;
+        last_index >>= 1;
+    }
+
+    last_index == 0 && hash == *root
+}
+
+/// Checks that the tree of `new_size` leaves whose head is `new_root` extends the one of
+/// `old_size` leaves whose head is `old_root`
+pub fn verify_consistency(
+    old_size: u64,
+    new_size: u64,
+    old_root: &Digest,
+    new_root: &Digest,
+    proof: &[Digest],
+) -> bool {
+    if old_size > new_size {
+        return false;
+    }
+
+    if old_size == new_size {
+        return proof.is_empty() && old_root == new_root;
+    }
+
+    if old_size == 0 {
+        return proof.is_empty();
+    }
+
+    // the old tree head is left out of the proof when the old tree is a complete subtree
+    let old_root_node = [*old_root];
+    let first_nodes: &[Digest] = match old_size.is_power_of_two() {
+        true => &old_root_node,
+        false => &[],
+    };
+    let mut nodes = first_nodes.iter().chain(proof);
+
+    let (mut node_index, mut last_index) = (old_size - 1, new_size - 1);
+
+    while node_index & 1 == 1 {
+        node_index >>= 1;
+        last_index >>= 1;
+    }
+
+    let Some(first) = nodes.next() else {
+        return false;
+    };
+    let (mut old_hash, mut new_hash) = (*first, *first);
+
+    for sibling in nodes {
+        if last_index == 0 {
+            return false;
+        }
+
+        if node_index & 1 == 1 || node_index == last_index {
+            old_hash = node_hash(sibling, &old_hash);
+            new_hash = node_hash(sibling, &new_hash);
+
+            while node_index & 1 == 0 && node_index != 0 {
+                node_index >>= 1;
+                last_index >>= 1;
+            }
+        } else {
+            new_hash = node_hash(&new_hash, sibling);
+        }
+
+        node_index >>= 1;
+        last_index >>= 1;
+    }
+
+    last_index == 0 && old_hash == *old_root && new_hash == *new_root
+}
+
+/// Largest power of two smaller than `n`, for n > 1
+fn split_point(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+fn tree_head(leaves: &[Digest]) -> Digest {
+    match leaves {
+        [] => Sha256::new().finalize(),
+        [leaf] => *leaf,
+        _ => {
+            let (left, right) = leaves.split_at(split_point(leaves.len()));
+
+            node_hash(&tree_head(left), &tree_head(right))
+        }
+    }
+}
+
+fn audit_path(index: usize, leaves: &[Digest], proof: &mut Vec<Digest>) {
+    if leaves.len() <= 1 {
+        return;
+    }
+
+    let (left, right) = leaves.split_at(split_point(leaves.len()));
+
+    if index < left.len() {
+        audit_path(index, left, proof);
+        proof.push(tree_head(right));
+    } else {
+        audit_path(index - left.len(), right, proof);
+        proof.push(tree_head(left));
+    }
+}
+
+fn subproof(old_size: usize, leaves: &[Digest], is_old_tree: bool, proof: &mut Vec<Digest>) {
+    if old_size == leaves.len() {
+        if !is_old_tree {
+            proof.push(tree_head(leaves));
+        }
+        return;
+    }
+
+    let (left, right) = leaves.split_at(split_point(leaves.len()));
+
+    if old_size <= left.len() {
+        subproof(old_size, left, is_old_tree, proof);
+        proof.push(tree_head(right));
+    } else {
+        subproof(old_size - left.len(), right, false, proof);
+        proof.push(tree_head(left));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    ////////////////// functions for setting up unit tests scenarios
+    fn digest(hex: &str) -> Digest {
+        Digest::from_hex(hex).unwrap()
+    }
+
+    fn digests(hexes: &[&str]) -> Vec<Digest> {
+        hexes.iter().map(|hex| digest(hex)).collect()
+    }
+
+    // leaves of the Certificate Transparency reference tests
+    fn get_tree() -> MerkleTree {
+        let leaves = [
+            "",
+            "00",
+            "10",
+            "2021",
+            "3031",
+            "40414243",
+            "5051525354555657",
+            "606162636465666768696a6b6c6d6e6f",
+        ];
+
+        let mut tree = MerkleTree::new();
+
+        for leaf in leaves {
+            let data: Vec<u8> = (0..leaf.len())
+                .step_by(2)
+                .map(|i| u8::from_str_radix(&leaf[i..i + 2], 16).unwrap())
+                .collect();
+
+            tree.push(&data);
+        }
+
+        tree
+    }
+
+    fn get_roots() -> Vec<Digest> {
+        digests(&[
+            "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+            "fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125",
+            "aeb6bcfe274b70a14fb067a5e5578264db0fa9b51af5e0ba159158f329e06e77",
+            "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+            "4e3bbb1f7b478dcfe71fb631631519a3bca12c9aefca1612bfce4c13a86264d4",
+            "76e67dadbcdf1e10e1b74ddc608abd2f98dfb16fbce75277b5232a127f2087ef",
+            "ddb89be403809e325750d3d263cd78929c2942b7942a34b77e122c9594a74c8c",
+            "5dc9da79a70659a9ad559cb701ded9a2ab9d823aad2f4960cfe370eff4604328",
+        ])
+    }
+
+    ////////////////// unit tests
+    #[test]
+    fn tree_heads() {
+        let tree = get_tree();
+
+        assert_eq!(
+            MerkleTree::new().root().to_hex(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+
+        for (size, root) in get_roots().iter().enumerate() {
+            assert_eq!(tree.root_at(size + 1).as_ref(), Some(root));
+        }
+
+        assert_eq!(tree.root(), get_roots()[7]);
+        assert_eq!(tree.root_at(9), None);
+    }
+
+    #[test]
+    fn inclusion_proofs() {
+        let tree = get_tree();
+        let roots = get_roots();
+
+        // (index, tree size, audit path)
+        let cases = [
+            (0, 1, vec![]),
+            (
+                0,
+                8,
+                digests(&[
+                    "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+                    "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                    "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+                ]),
+            ),
+            (
+                5,
+                8,
+                digests(&[
+                    "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+                    "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+                    "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+                ]),
+            ),
+            (
+                2,
+                3,
+                digests(&["fac54203e7cc696cf0dfcb42c92a1d9dbaf70ad9e621f4bd8d98662f00e3c125"]),
+            ),
+            (
+                1,
+                5,
+                digests(&[
+                    "6e340b9cffb37a989ca544e6bb780a2c78901d3fb33738768511a30617afa01d",
+                    "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                    "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+                ]),
+            ),
+        ];
+
+        for (index, size, path) in cases {
+            assert_eq!(tree.inclusion_proof(index, size), Some(path.clone()));
+
+            let leaf = tree.leaf_hash(index).unwrap();
+            let root = &roots[size - 1];
+            assert!(verify_inclusion(
+                &leaf,
+                index as u64,
+                size as u64,
+                &path,
+                root
+            ));
+
+            // wrong index, leaf or truncated path
+            assert!(!verify_inclusion(
+                &leaf,
+                index as u64 + 1,
+                size as u64,
+                &path,
+                root
+            ));
+            let other_leaf = leaf_hash(b"not a leaf");
+            assert!(!verify_inclusion(
+                &other_leaf,
+                index as u64,
+                size as u64,
+                &path,
+                root
+            ));
+            if !path.is_empty() {
+                assert!(!verify_inclusion(
+                    &leaf,
+                    index as u64,
+                    size as u64,
+                    &path[1..],
+                    root
+                ));
+            }
+        }
+
+        assert_eq!(tree.inclusion_proof(8, 8), None);
+        assert_eq!(tree.inclusion_proof(0, 9), None);
+    }
+
+    #[test]
+    fn every_inclusion_proof_verifies() {
+        let tree = get_tree();
+
+        for size in 1..=tree.len() {
+            let root = tree.root_at(size).unwrap();
+
+            for index in 0..size {
+                let proof = tree.inclusion_proof(index, size).unwrap();
+                let leaf = tree.leaf_hash(index).unwrap();
+
+                assert!(verify_inclusion(
+                    &leaf,
+                    index as u64,
+                    size as u64,
+                    &proof,
+                    &root
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn consistency_proofs() {
+        let tree = get_tree();
+        let roots = get_roots();
+
+        // (old size, new size, proof)
+        let cases = [
+            (1, 1, vec![]),
+            (
+                1,
+                8,
+                digests(&[
+                    "96a296d224f285c67bee93c30f8a309157f0daa35dc5b87e410b78630a09cfc7",
+                    "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                    "6b47aaf29ee3c2af9af889bc1fb9254dabd31177f16232dd6aab035ca39bf6e4",
+                ]),
+            ),
+            (
+                6,
+                8,
+                digests(&[
+                    "0ebc5d3437fbe2db158b9f126a1d118e308181031d0a949f8dededebc558ef6a",
+                    "ca854ea128ed050b41b35ffc1b87b8eb2bde461e9e3b5596ece6b9d5975a0ae0",
+                    "d37ee418976dd95753c1c73862b9398fa2a2cf9b4ff0fdfe8b30cd95209614b7",
+                ]),
+            ),
+            (
+                2,
+                5,
+                digests(&[
+                    "5f083f0a1a33ca076a95279832580db3e0ef4584bdff1f54c8a360f50de3031e",
+                    "bc1a0643b12e4d2d7c77918f44e0f4f79a838b6cf9ec5b5c283e1f4d88599e6b",
+                ]),
+            ),
+        ];
+
+        for (old_size, new_size, proof) in cases {
+            assert_eq!(
+                tree.consistency_proof(old_size, new_size),
+                Some(proof.clone())
+            );
+
+            let (old_root, new_root) = (&roots[old_size - 1], &roots[new_size - 1]);
+            let (old, new) = (old_size as u64, new_size as u64);
+            assert!(verify_consistency(old, new, old_root, new_root, &proof));
+
+            if old_size != new_size {
+                // swapped roots, wrong old size or truncated proof
+                assert!(!verify_consistency(old, new, new_root, old_root, &proof));
+                assert!(!verify_consistency(
+                    old + 1,
+                    new,
+                    old_root,
+                    new_root,
+                    &proof
+                ));
+                assert!(!verify_consistency(
+                    old,
+                    new,
+                    old_root,
+                    new_root,
+                    &proof[1..]
+                ));
+            }
+        }
+
+        assert_eq!(tree.consistency_proof(3, 2), None);
+        assert_eq!(tree.consistency_proof(1, 9), None);
+    }
+
+    #[test]
+    fn every_consistency_proof_verifies() {
+        let tree = get_tree();
+
+        for new_size in 0..=tree.len() {
+            for old_size in 0..=new_size {
+                let proof = tree.consistency_proof(old_size, new_size).unwrap();
+                let old_root = tree.root_at(old_size).unwrap();
+                let new_root = tree.root_at(new_size).unwrap();
+
+                assert!(verify_consistency(
+                    old_size as u64,
+                    new_size as u64,
+                    &old_root,
+                    &new_root,
+                    &proof
+                ));
+            }
+        }
+    }
+
+    #[test]
+    fn leaf_and_node_are_domain_separated() {
+        let (left, right) = (leaf_hash(b"a"), leaf_hash(b"b"));
+
+        let mut concatenation = left.as_bytes().to_vec();
+        concatenation.extend(right.as_bytes());
+
+        assert_ne!(node_hash(&left, &right), leaf_hash(&concatenation));
+    }
+}
@@ -0,0 +1,169 @@
+//!    This module describes how `pre_process` pads a message into 512-bits blocks:
+//!    the message, a 0x80 byte (a single 1 bit), zero bytes up to 56 bytes modulo 64 (448 bits
+//!    modulo 512), then the original length in bits as a big-endian 64 bits integer.
+
+use crate::error::Error;
+
+/// Padding appended to a message of `msg_len` bytes, whatever its content: 0x80, zeros up to
+/// 56 bytes in the last block, then the length in bits as 64 bits (modulo 2^64).
+/// `pre_process` and `Sha256::finalize` pad with it, and length extensions glue it to the
+/// known message.
+pub fn glue_padding(msg_len: u64) -> Vec<u8> {
+    // the padding only depends on the message length, so it is computed without the message
+    let nb_zero_bytes = ((64 + 56 - (msg_len % 64 + 1)) % 64) as usize;
+    let length_bits = msg_len.wrapping_mul(8);
+
+    let mut padding = vec![0x80];
+    padding.resize(1 + nb_zero_bytes, 0);
+    padding.extend(length_bits.to_be_bytes());
+
+    padding
+}
+
+/// Part of the padded message a byte belongs to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Message,
+    Marker, // the 0x80 byte
+    Zero,
+    Length,
+}
+
+/// Padded message, along with where each part of the padding is
+#[derive(Debug, Clone, PartialEq)]
+pub struct PaddingLayout {
+    pub padded: Vec<u8>,
+    pub msg_len: usize,       // in bytes
+    pub length_bits: u64,     // value of the length field
+    pub marker_offset: usize, // equals msg_len
+    pub zero_bytes: usize,
+    pub length_offset: usize,
+}
+
+impl PaddingLayout {
+    pub fn new(msg: Vec<u8>) -> PaddingLayout {
+        let msg_len = msg.len();
+        let padded = crate::pre_process(msg);
+
+        let length_offset = padded.len() - 8;
+        let mut length_field = [0u8; 8];
+        length_field.copy_from_slice(&padded[length_offset..]);
+
+        PaddingLayout {
+            msg_len,
+            length_bits: u64::from_be_bytes(length_field),
+            marker_offset: msg_len,
+            zero_bytes: length_offset - msg_len - 1,
+            length_offset,
+            padded,
+        }
+    }
+
+    pub fn nb_blocks(&self) -> usize {
+        self.padded.len() / 64
+    }
+
+    /// 64 bytes of the block `index`
+    pub fn block(&self, index: usize) -> Result<&[u8], Error> {
+        crate::parse_block(&self.padded, index)
+    }
+
+    /// Whether the length field didn't fit in the block of the 0x80 byte, adding a block
+    pub fn needs_extra_block(&self) -> bool {
+        self.marker_offset / 64 != self.length_offset / 64
+    }
+
+    /// Part of the padded message the byte at `offset` belongs to
+    pub fn region(&self, offset: usize) -> Region {
+        if offset < self.marker_offset {
+            Region::Message
+        } else if offset == self.marker_offset {
+            Region::Marker
+        } else if offset < self.length_offset {
+            Region::Zero
+        } else {
+            Region::Length
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glue_padding_test() {
+        let mut padding_good = vec![0x80];
+        padding_good.extend([0; 52]);
+        padding_good.extend(24u64.to_be_bytes());
+
+        assert_eq!(glue_padding(3), padding_good);
+
+        for msg_len in [0, 1, 55, 56, 63, 64, 65, 119, 120, 1000] {
+            let padding = glue_padding(msg_len);
+            assert_eq!((msg_len + padding.len() as u64) % 64, 0, "{msg_len} bytes");
+            assert_eq!(padding[padding.len() - 8..], (msg_len * 8).to_be_bytes());
+        }
+
+        // the length field is the length in bits modulo 2^64
+        assert_eq!(glue_padding(1 << 61)[56..], [0; 8]);
+
+        // the length field doesn't fit after 56 bytes, a whole block is added
+        assert_eq!(glue_padding(55).len(), 9);
+        assert_eq!(glue_padding(56).len(), 72);
+        assert_eq!(glue_padding(64).len(), 64);
+    }
+
+    #[test]
+    fn abc_layout() {
+        let layout = PaddingLayout::new(b"abc".to_vec());
+
+        assert_eq!(layout.nb_blocks(), 1);
+        assert_eq!(layout.length_bits, 24);
+        assert_eq!(layout.marker_offset, 3);
+        assert_eq!(layout.zero_bytes, 52);
+        assert_eq!(layout.length_offset, 56);
+        assert!(!layout.needs_extra_block());
+
+        assert_eq!(layout.block(0).unwrap()[3], 0x80);
+        assert_eq!(layout.block(0).unwrap()[63], 24);
+        assert_eq!(
+            layout.block(1),
+            Err(Error::BlockOutOfRange {
+                index: 1,
+                nb_blocks: 1
+            })
+        );
+
+        assert_eq!(layout.region(2), Region::Message);
+        assert_eq!(layout.region(3), Region::Marker);
+        assert_eq!(layout.region(55), Region::Zero);
+        assert_eq!(layout.region(56), Region::Length);
+    }
+
+    #[test]
+    fn block_boundaries() {
+        // (message length, number of blocks, number of zero bytes)
+        let layouts = [
+            (0, 1, 55),
+            (55, 1, 0),
+            (56, 2, 63),
+            (63, 2, 56),
+            (64, 2, 55),
+            (119, 2, 0),
+            (120, 3, 63),
+        ];
+
+        for (msg_len, nb_blocks, zero_bytes) in layouts {
+            let layout = PaddingLayout::new(vec![0xff; msg_len]);
+
+            assert_eq!(layout.nb_blocks(), nb_blocks, "{msg_len} bytes");
+            assert_eq!(layout.zero_bytes, zero_bytes, "{msg_len} bytes");
+            assert_eq!(layout.length_bits, msg_len as u64 * 8);
+            assert_eq!(layout.padded.len() % 64, 0);
+        }
+
+        assert!(PaddingLayout::new(vec![0; 56]).needs_extra_block());
+        assert!(!PaddingLayout::new(vec![0; 64]).needs_extra_block());
+    }
+}
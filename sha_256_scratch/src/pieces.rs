@@ -0,0 +1,142 @@
+//!    This module computes piece hashes: the SHA-256 of every consecutive `piece_size` bytes of
+//!    a message (the last piece may be shorter), along with the SHA-256 of the whole message.
+//!    Both are computed from a single pass over the data, which is only read once.
+//!    An empty message has no pieces.
+
+use crate::error::Error;
+use crate::hasher::{Digest, Sha256};
+use std::mem;
+
+/// Piece hashes and whole message digest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pieces {
+    pub piece_size: usize,
+    pub pieces: Vec<Digest>,
+    pub whole: Digest,
+}
+
+/// Incremental piece hashing
+pub struct PieceHasher {
+    piece_size: usize,
+    piece: Sha256,
+    piece_len: usize,
+    whole: Sha256,
+    pieces: Vec<Digest>,
+}
+
+impl PieceHasher {
+    pub fn new(piece_size: usize) -> Result<PieceHasher, Error> {
+        if piece_size == 0 {
+            return Err(Error::ZeroPieceSize);
+        }
+
+        Ok(PieceHasher {
+            piece_size,
+            piece: Sha256::new(),
+            piece_len: 0,
+            whole: Sha256::new(),
+            pieces: Vec::new(),
+        })
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.whole.update(data);
+
+        while !data.is_empty() {
+            let taken = data.len().min(self.piece_size - self.piece_len);
+
+            self.piece.update(&data[..taken]);
+            self.piece_len += taken;
+            data = &data[taken..];
+
+            if self.piece_len == self.piece_size {
+                self.finish_piece();
+            }
+        }
+    }
+
+    pub fn finalize(mut self) -> Pieces {
+        if self.piece_len > 0 {
+            self.finish_piece();
+        }
+
+        Pieces {
+            piece_size: self.piece_size,
+            pieces: self.pieces,
+            whole: self.whole.finalize(),
+        }
+    }
+
+    fn finish_piece(&mut self) {
+        let piece = mem::take(&mut self.piece);
+
+        self.pieces.push(piece.finalize());
+        self.piece_len = 0;
+    }
+}
+
+pub fn piece_hashes(msg: &[u8], piece_size: usize) -> Result<Pieces, Error> {
+    let mut hasher = PieceHasher::new(piece_size)?;
+    hasher.update(msg);
+
+    Ok(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(msg: &[u8]) -> Digest {
+        let mut hasher = Sha256::new();
+        hasher.update(msg);
+
+        hasher.finalize()
+    }
+
+    fn get_msg() -> Vec<u8> {
+        (0..1000).map(|i| (i * 7 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn pieces_match_chunks() {
+        let msg = get_msg();
+
+        for piece_size in [1, 64, 100, 999, 1000, 1001] {
+            let pieces = piece_hashes(&msg, piece_size).unwrap();
+
+            let pieces_good: Vec<Digest> = msg.chunks(piece_size).map(digest).collect();
+
+            assert_eq!(pieces.pieces, pieces_good);
+            assert_eq!(pieces.whole, digest(&msg));
+        }
+    }
+
+    #[test]
+    fn data_given_in_pieces() {
+        let msg = get_msg();
+
+        let mut hasher = PieceHasher::new(128).unwrap();
+        for part in msg.chunks(37) {
+            hasher.update(part);
+        }
+
+        assert_eq!(hasher.finalize(), piece_hashes(&msg, 128).unwrap());
+    }
+
+    #[test]
+    fn empty_msg_has_no_pieces() {
+        let pieces = piece_hashes(b"", 64).unwrap();
+
+        assert!(pieces.pieces.is_empty());
+        assert_eq!(
+            pieces.whole.to_hex(),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn zero_piece_size() {
+        assert!(matches!(PieceHasher::new(0), Err(Error::ZeroPieceSize)));
+        assert_eq!(piece_hashes(b"msg", 0), Err(Error::ZeroPieceSize));
+    }
+}
@@ -0,0 +1,333 @@
+//!    This module defines parameterised SHA-256 variants for cryptanalysis experiments:
+//!    fewer rounds, other round constants K, another IV, or no feed-forward (the addition of
+//!    the chaining value to the compressed block in `add_compressed_chunk_in_hash`).
+//!
+//!    THESE VARIANTS ARE NOT SECURE HASH FUNCTIONS and must never be used outside of research.
+//!    The module only exists with the `reduced-rounds` feature.
+
+use crate::error::Error;
+use crate::hasher::Digest;
+use crate::math;
+use crate::working_variables::WorkingVariables;
+
+/// SHA-256 with custom parameters, the standard algorithm when created with 64 rounds
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReducedSha256 {
+    rounds: usize,
+    k: [u32; 64],
+    iv: [u32; 8],
+    feed_forward: bool,
+}
+
+impl ReducedSha256 {
+    /// Standard constants, IV and feed-forward, with only the first `rounds` rounds (1 to 64)
+    pub fn new(rounds: usize) -> Result<ReducedSha256, Error> {
+        if !(1..=64).contains(&rounds) {
+            return Err(Error::InvalidRounds(rounds));
+        }
+
+        Ok(ReducedSha256 {
+            rounds,
+            k: math::K,
+            iv: math::H_0,
+            feed_forward: true,
+        })
+    }
+
+    /// Replaces the round constants, only the first `rounds` ones are used
+    pub fn with_k(mut self, k: [u32; 64]) -> ReducedSha256 {
+        self.k = k;
+        self
+    }
+
+    /// Replaces the initial hash values H(0)
+    pub fn with_iv(mut self, iv: [u32; 8]) -> ReducedSha256 {
+        self.iv = iv;
+        self
+    }
+
+    /// The compression function then returns the working variables after the last round,
+    /// without adding the chaining value to them
+    pub fn without_feed_forward(mut self) -> ReducedSha256 {
+        self.feed_forward = false;
+        self
+    }
+
+    pub fn rounds(&self) -> usize {
+        self.rounds
+    }
+
+    /// Compresses one 64-bytes block into the chaining value
+    pub fn compress(&self, chaining_value: &[u32; 8], block: &[u8]) -> Result<[u32; 8], Error> {
+        if block.len() != 64 {
+            return Err(Error::InvalidBlockLength(block.len()));
+        }
+
+        Ok(self.compress_block(chaining_value, block))
+    }
+
+    fn compress_block(&self, chaining_value: &[u32; 8], block: &[u8]) -> [u32; 8] {
+        let working_var = WorkingVariables::from_array(chaining_value);
+        let schedule = crate::message_schedule(block);
+
+        let compressed = crate::compress_chunk(working_var, schedule, &self.k[..self.rounds]);
+
+        match self.feed_forward {
+            true => crate::add_compressed_chunk_in_hash(chaining_value, &compressed),
+            false => [
+                compressed.a,
+                compressed.b,
+                compressed.c,
+                compressed.d,
+                compressed.e,
+                compressed.f,
+                compressed.g,
+                compressed.h,
+            ],
+        }
+    }
+
+    /// Pads the message as `pre_process` does and compresses every block from the IV
+    pub fn hash(&self, msg: &[u8]) -> Digest {
+        let padded = crate::pre_process(msg.to_vec());
+
+        let hash = padded
+            .chunks_exact(64)
+            .fold(self.iv, |hash, block| self.compress_block(&hash, block));
+
+        Digest::from_hash(hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block_with_words(words: &[u32; 16]) -> Vec<u8> {
+        words.iter().flat_map(|word| word.to_be_bytes()).collect()
+    }
+
+    #[test]
+    fn rounds_range() {
+        assert_eq!(ReducedSha256::new(0), Err(Error::InvalidRounds(0)));
+        assert_eq!(ReducedSha256::new(65), Err(Error::InvalidRounds(65)));
+        assert_eq!(ReducedSha256::new(1).unwrap().rounds(), 1);
+        assert_eq!(ReducedSha256::new(64).unwrap().rounds(), 64);
+    }
+
+    #[test]
+    fn full_rounds_is_sha_256() {
+        let sha = ReducedSha256::new(64).unwrap();
+
+        for msg in [&b""[..], b"abc", &[0x61; 200]] {
+            assert_eq!(sha.hash(msg).to_hex(), crate::sha_256(msg.to_vec()));
+        }
+
+        let sha_224 = sha.with_iv(math::H_0_224);
+        assert_eq!(
+            &sha_224.hash(b"abc").to_hex()[..56],
+            crate::sha_224(b"abc".to_vec())
+        );
+    }
+
+    // working variables of the "abc" example in FIPS 180-2, appendix B.1
+    #[test]
+    fn fips_180_2_intermediate_values() {
+        let block = crate::pre_process(b"abc".to_vec());
+
+        let one_round = ReducedSha256::new(1).unwrap().without_feed_forward();
+        assert_eq!(
+            one_round.compress(&math::H_0, &block).unwrap(),
+            [
+                0x5d6aebcd, 0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xfa2a4622, 0x510e527f, 0x9b05688c,
+                0x1f83d9ab
+            ]
+        );
+
+        let all_rounds = ReducedSha256::new(64).unwrap().without_feed_forward();
+        assert_eq!(
+            all_rounds.compress(&math::H_0, &block).unwrap(),
+            [
+                0x506e3058, 0xd39a2165, 0x04d24d6c, 0xb85e2ce9, 0x5ef50f24, 0xfb121210, 0x948d25b6,
+                0x961f4894
+            ]
+        );
+    }
+
+    // up to 15 rounds, the last message word W[15] isn't used: changing it is a collision
+    #[test]
+    fn trivial_collision_below_16_rounds() {
+        let mut words = [0x01234567u32; 16];
+        let block = block_with_words(&words);
+        words[15] ^= 0x80000000;
+        let other_block = block_with_words(&words);
+
+        for rounds in 1..=15 {
+            let sha = ReducedSha256::new(rounds).unwrap();
+
+            assert_eq!(
+                sha.compress(&math::H_0, &block).unwrap(),
+                sha.compress(&math::H_0, &other_block).unwrap()
+            );
+        }
+
+        let sha = ReducedSha256::new(16).unwrap();
+        assert_ne!(
+            sha.compress(&math::H_0, &block).unwrap(),
+            sha.compress(&math::H_0, &other_block).unwrap()
+        );
+    }
+
+    /// Message words making the working variable `a` take the value `a_targets[t]` after round t:
+    /// W[t] is added into T1, so up to round 15 it sets the new `a` to any value
+    fn words_for_a_values(a_targets: &[u32; 16]) -> [u32; 16] {
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = math::H_0;
+        let mut words = [0u32; 16];
+
+        for t in 0..16 {
+            let t2 = math::big_sigma_0(a).wrapping_add(math::majority(a, b, c));
+            let t1 = a_targets[t].wrapping_sub(t2);
+
+            words[t] = t1
+                .wrapping_sub(h)
+                .wrapping_sub(math::big_sigma_1(e))
+                .wrapping_sub(math::choice(e, f, g))
+                .wrapping_sub(math::K[t]);
+
+            (h, g, f, e) = (g, f, e, d.wrapping_add(t1));
+            (d, c, b, a) = (c, b, a, a_targets[t]);
+        }
+
+        words
+    }
+
+    // a 9-round local collision: e after round t depends on a from rounds t-4 to t, so a
+    // difference in a after round 7 alone is gone from the whole state after round 15.
+    // Both blocks differ in the 9 words W[7..=15], and only collide while W[16] isn't used.
+    #[test]
+    fn local_collision_in_16_rounds() {
+        let a_targets: [u32; 16] = std::array::from_fn(|t| (t as u32 + 1).wrapping_mul(0x9e3779b9));
+        let mut other_targets = a_targets;
+        other_targets[7] ^= 0x0f0f0f0f;
+
+        let words = words_for_a_values(&a_targets);
+        let other_words = words_for_a_values(&other_targets);
+
+        let differing: Vec<usize> = (0..16).filter(|&t| words[t] != other_words[t]).collect();
+        assert_eq!(differing, (7..16).collect::<Vec<_>>());
+
+        let (block, other_block) = (block_with_words(&words), block_with_words(&other_words));
+
+        let sha = ReducedSha256::new(16).unwrap();
+        assert_eq!(
+            sha.compress(&math::H_0, &block).unwrap(),
+            sha.compress(&math::H_0, &other_block).unwrap()
+        );
+
+        let sha = ReducedSha256::new(17).unwrap();
+        assert_ne!(
+            sha.compress(&math::H_0, &block).unwrap(),
+            sha.compress(&math::H_0, &other_block).unwrap()
+        );
+    }
+
+    // a 21-step collision from the standard IV, built with the local collision of Nikolić and
+    // Biryukov ("Collisions for Step-Reduced SHA-256", FSE 2008) over rounds 5 to 13: the blocks
+    // differ by d = 0x17297eeb in W[5] and -d in W[13], and W[6..=8] cancel the state differences.
+    // W[5] and the state after rounds 5 and 9 satisfy sigma_0(W + d) - sigma_0(W) = d,
+    // Sigma_0(a + d) - Sigma_0(a) = d and Sigma_1(e + d) - Sigma_1(e) = d, so the differences also
+    // cancel in W[16..=20]. This pair was generated here, it isn't the one printed in the paper.
+    #[test]
+    fn collision_in_21_rounds() {
+        let block = block_with_words(&[
+            0x71ec521d, 0x9ddc1c04, 0x162a9644, 0xfb7b6e3d, 0xc32def48, 0xc3592acb, 0xa6c08cb7,
+            0xc27abc75, 0x7129117a, 0xf357b33a, 0xe9952a4f, 0xa704ce03, 0x11c92580, 0xf9eacb60,
+            0x0678f13c, 0xb286f234,
+        ]);
+        let other_block = block_with_words(&[
+            0x71ec521d, 0x9ddc1c04, 0x162a9644, 0xfb7b6e3d, 0xc32def48, 0xda82a9b6, 0xf60694d4,
+            0x14cf339e, 0x5a00c68b, 0xf357b33a, 0xe9952a4f, 0xa704ce03, 0x11c92580, 0xe2c14c75,
+            0x0678f13c, 0xb286f234,
+        ]);
+
+        let sha = ReducedSha256::new(21).unwrap();
+        let chaining_value = [
+            0x186f7108, 0x5e7577c6, 0xbd73dda3, 0xcb95f2e9, 0xc9110d93, 0xafc5680d, 0x36eec8e9,
+            0xb9b27463,
+        ];
+        assert_eq!(sha.compress(&math::H_0, &block).unwrap(), chaining_value);
+        assert_eq!(
+            sha.compress(&math::H_0, &other_block).unwrap(),
+            chaining_value
+        );
+
+        // W[21] uses sigma_0(W[6]), whose difference nothing cancels
+        let sha = ReducedSha256::new(22).unwrap();
+        assert_ne!(
+            sha.compress(&math::H_0, &block).unwrap(),
+            sha.compress(&math::H_0, &other_block).unwrap()
+        );
+    }
+
+    // K[t] and W[t] are both added into T1, so moving a difference from W[0] to K[0]
+    // doesn't change the output while W[0] isn't used by the schedule (16 rounds)
+    #[test]
+    fn custom_constants() {
+        let words = [0x89abcdefu32; 16];
+        let mut shifted_words = words;
+        shifted_words[0] = shifted_words[0].wrapping_sub(0x1000);
+
+        let mut k = math::K;
+        k[0] = k[0].wrapping_add(0x1000);
+
+        let standard = ReducedSha256::new(16).unwrap();
+        let custom = ReducedSha256::new(16).unwrap().with_k(k);
+
+        assert_eq!(
+            standard
+                .compress(&math::H_0, &block_with_words(&words))
+                .unwrap(),
+            custom
+                .compress(&math::H_0, &block_with_words(&shifted_words))
+                .unwrap()
+        );
+
+        let custom = ReducedSha256::new(17).unwrap().with_k(k);
+        assert_ne!(
+            ReducedSha256::new(17)
+                .unwrap()
+                .compress(&math::H_0, &block_with_words(&words))
+                .unwrap(),
+            custom
+                .compress(&math::H_0, &block_with_words(&shifted_words))
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn block_length() {
+        let sha = ReducedSha256::new(64).unwrap();
+
+        assert_eq!(
+            sha.compress(&math::H_0, &[0; 63]),
+            Err(Error::InvalidBlockLength(63))
+        );
+    }
+
+    #[test]
+    fn feed_forward() {
+        let block = crate::pre_process(b"abc".to_vec());
+        let sha = ReducedSha256::new(8).unwrap();
+
+        let with = sha.compress(&math::H_0, &block).unwrap();
+        let without = sha
+            .clone()
+            .without_feed_forward()
+            .compress(&math::H_0, &block)
+            .unwrap();
+
+        for i in 0..8 {
+            assert_eq!(with[i], without[i].wrapping_add(math::H_0[i]));
+        }
+    }
+}
@@ -0,0 +1,140 @@
+//!    This module defines the tagged hashes of BIP-340, used by Bitcoin Schnorr signatures and Taproot:
+//!    tagged_hash(tag, m) = SHA256(SHA256(tag) || SHA256(tag) || m)
+//!    The prefix SHA256(tag) || SHA256(tag) is exactly one 512-bits block, so the hasher state
+//!    after it (the midstate) is computed once per tag and reused for every message.
+
+use crate::hasher::{Digest, Sha256};
+
+/// Tagged hasher for one tag, holding the midstate after the tag prefix
+#[derive(Clone)]
+pub struct TaggedHasher {
+    midstate: Sha256,
+}
+
+impl TaggedHasher {
+    pub fn new(tag: &[u8]) -> TaggedHasher {
+        let mut tag_hasher = Sha256::new();
+        tag_hasher.update(tag);
+        let tag_hash = tag_hasher.finalize();
+
+        let mut midstate = Sha256::new();
+        midstate.update(tag_hash.as_bytes());
+        midstate.update(tag_hash.as_bytes());
+
+        TaggedHasher { midstate }
+    }
+
+    /// Incremental hasher whose tag prefix is already hashed, only the message is left to give
+    pub fn hasher(&self) -> Sha256 {
+        self.midstate.clone()
+    }
+
+    pub fn hash(&self, msg: &[u8]) -> Digest {
+        let mut hasher = self.hasher();
+        hasher.update(msg);
+
+        hasher.finalize()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tagged_hash_from_definition(tag: &[u8], msg: &[u8]) -> Digest {
+        let mut tag_hasher = Sha256::new();
+        tag_hasher.update(tag);
+        let tag_hash = tag_hasher.finalize();
+
+        let mut prefixed = tag_hash.as_bytes().repeat(2);
+        prefixed.extend(msg);
+
+        let mut hasher = Sha256::new();
+        hasher.update(&prefixed);
+
+        hasher.finalize()
+    }
+
+    // expected values computed with an independent SHA-256 implementation (Python hashlib)
+    #[test]
+    fn bip_340_tags() {
+        let challenge = TaggedHasher::new(b"BIP0340/challenge");
+        assert_eq!(
+            challenge.hash(b"").to_hex(),
+            "c216d352f5818b7b4beacd4ae0a26fe888080823d2a598856661bcd54f1b3713"
+        );
+
+        let aux = TaggedHasher::new(b"BIP0340/aux");
+        assert_eq!(
+            aux.hash(&[0; 32]).to_hex(),
+            "54f169cfc9e2e5727480441f90ba25c488f461c70b5ea5dcaaf7af69270aa514"
+        );
+
+        let tap_leaf = TaggedHasher::new(b"TapLeaf");
+        assert_eq!(
+            tap_leaf.hash(b"hello").to_hex(),
+            "cc0d501f9c9c9610e44edb939ad98d59a1a98739d0e26dbe932660ae33a8196e"
+        );
+    }
+
+    // vector 0 of BIP-340's test-vectors.csv: secret key 3, aux_rand and message all zeros,
+    // signature E907831F...2DCA8215 25F66A4A...310536C0.
+    // Signing it with these hashes (aux, then nonce for R, then challenge for s) gives back
+    // exactly the published signature, so they are the ones the vector commits to.
+    #[test]
+    fn bip_340_vector_0() {
+        let public_key =
+            Digest::from_hex("f9308a019258c31049344f85f89d5229b531c845836f99b08601f113bce036f9")
+                .unwrap();
+        let r =
+            Digest::from_hex("e907831f80848d1069a5371b402410364bdf1c5f8307b0084c55f1ce2dca8215")
+                .unwrap();
+        let msg = [0u8; 32];
+
+        // t = secret key XOR aux hash, the secret key 3 flipping the last two bits
+        let aux = TaggedHasher::new(b"BIP0340/aux").hash(&[0; 32]);
+        let mut t = aux.as_bytes().to_vec();
+        t[31] ^= 3;
+        assert_eq!(
+            aux.to_hex(),
+            "54f169cfc9e2e5727480441f90ba25c488f461c70b5ea5dcaaf7af69270aa514"
+        );
+
+        // k = nonce hash mod n, and R = k·G
+        let nonce_input = [&t[..], public_key.as_bytes(), &msg].concat();
+        assert_eq!(
+            TaggedHasher::new(b"BIP0340/nonce")
+                .hash(&nonce_input)
+                .to_hex(),
+            "1d2dc1652fee3ad08434469f9ad30536a5787feccfa308e8fb396c8030dd1c69"
+        );
+
+        // e = tagged_hash("BIP0340/challenge", R || P || m) mod n, and s = k + e·d
+        let challenge_input = [r.as_bytes(), public_key.as_bytes(), &msg].concat();
+        assert_eq!(
+            TaggedHasher::new(b"BIP0340/challenge")
+                .hash(&challenge_input)
+                .to_hex(),
+            "6bb6b93a91f2ecc0cd924f4f9baabb5e6eb21745bb00f2cebdaac908bb5d86ce"
+        );
+    }
+
+    #[test]
+    fn midstate_is_reused() {
+        let tagged = TaggedHasher::new(b"BIP0340/nonce");
+
+        for len in [0, 1, 55, 64, 100, 1000] {
+            let msg = vec![0xab; len];
+
+            assert_eq!(
+                tagged.hash(&msg),
+                tagged_hash_from_definition(b"BIP0340/nonce", &msg)
+            );
+        }
+
+        let mut hasher = tagged.hasher();
+        hasher.update(b"hel");
+        hasher.update(b"lo");
+        assert_eq!(hasher.finalize(), tagged.hash(b"hello"));
+    }
+}
@@ -0,0 +1,153 @@
+//!    This module records the intermediate values of SHA-256, to follow the algorithm step by step.
+//!
+//!    For every 512-bits block of the padded message, the trace holds the block bytes, the 64 words
+//!    W[t] of the message schedule, the working variables a..h after each of the 64 rounds along
+//!    with the temporary words T1 and T2 of the round, and the chaining value: the hash values
+//!    after the compressed block has been added to them.
+
+use crate::hasher::Digest;
+use crate::math;
+use crate::working_variables::WorkingVariables;
+
+/// One round t of the compression function
+#[derive(Debug, Clone, PartialEq)]
+pub struct Round {
+    pub t: usize,
+    pub w: u32,
+    pub k: u32,
+    pub t1: u32,
+    pub t2: u32,
+    pub vars: WorkingVariables, // after the round
+}
+
+/// Compression of one block of the padded message
+#[derive(Debug, Clone, PartialEq)]
+pub struct BlockTrace {
+    pub block: Vec<u8>,     // 64 bytes
+    pub schedule: Vec<u32>, // W[0..64]
+    pub initial: WorkingVariables,
+    pub rounds: Vec<Round>,
+    pub chaining_value: [u32; 8],
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trace {
+    pub blocks: Vec<BlockTrace>,
+}
+
+impl Trace {
+    /// Hashes `msg` with SHA-256, recording every intermediate value
+    pub fn new(msg: &[u8]) -> Trace {
+        let padded = crate::pre_process(msg.to_vec());
+        let mut hash = math::H_0;
+        let mut blocks = Vec::new();
+
+        for block in (0..).map_while(|i| crate::parse_block(&padded, i).ok()) {
+            let block_trace = trace_block(block, &hash);
+            hash = block_trace.chaining_value;
+
+            blocks.push(block_trace);
+        }
+
+        Trace { blocks }
+    }
+
+    /// Final hash: the chaining value after the last block, `None` if there is no block
+    pub fn digest(&self) -> Option<Digest> {
+        let last = self.blocks.last()?;
+
+        Some(Digest::from_hash(last.chaining_value))
+    }
+}
+
+fn trace_block(block: &[u8], hash: &[u32; 8]) -> BlockTrace {
+    let schedule = crate::message_schedule(block);
+    let initial = WorkingVariables::from_array(hash);
+
+    let mut current = initial.clone();
+    let mut rounds = Vec::with_capacity(64);
+
+    for (t, (&w, &k)) in schedule.iter().zip(&math::K).enumerate() {
+        let (t1, t2) = crate::compress_round(&mut current, w, k);
+        let vars = current.clone();
+
+        rounds.push(Round {
+            t,
+            w,
+            k,
+            t1,
+            t2,
+            vars,
+        });
+    }
+
+    let chaining_value = crate::add_compressed_chunk_in_hash(hash, &current);
+
+    BlockTrace {
+        block: block.to_vec(),
+        schedule,
+        initial,
+        rounds,
+        chaining_value,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // intermediate values of the "abc" example in FIPS 180-2, appendix B.1
+    #[test]
+    fn fips_180_2_abc_example() {
+        let trace = Trace::new(b"abc");
+        assert_eq!(trace.blocks.len(), 1);
+
+        let block = &trace.blocks[0];
+        assert_eq!(&block.block[..4], &[0x61, 0x62, 0x63, 0x80]);
+        assert_eq!(block.schedule[0], 0x61626380);
+        assert_eq!(block.schedule[15], 0x00000018);
+        assert_eq!(block.initial, WorkingVariables::from_array(&math::H_0));
+
+        let first = WorkingVariables::from_array(&[
+            0x5d6aebcd, 0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xfa2a4622, 0x510e527f, 0x9b05688c,
+            0x1f83d9ab,
+        ]);
+        assert_eq!(block.rounds[0].vars, first);
+
+        let last = WorkingVariables::from_array(&[
+            0x506e3058, 0xd39a2165, 0x04d24d6c, 0xb85e2ce9, 0x5ef50f24, 0xfb121210, 0x948d25b6,
+            0x961f4894,
+        ]);
+        assert_eq!(block.rounds[63].vars, last);
+
+        assert_eq!(
+            trace.digest().unwrap().to_hex(),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn rounds_are_consistent() {
+        let msg = vec![b'x'; 100];
+        let trace = Trace::new(&msg);
+        assert_eq!(trace.blocks.len(), 2);
+
+        for block in &trace.blocks {
+            assert_eq!(block.rounds.len(), 64);
+
+            for round in &block.rounds {
+                assert_eq!(round.vars.a, round.t1.wrapping_add(round.t2));
+                assert_eq!(round.w, block.schedule[round.t]);
+            }
+        }
+
+        // the second block starts from the chaining value of the first one
+        let chaining_value = trace.blocks[0].chaining_value;
+        assert_eq!(
+            trace.blocks[1].initial,
+            WorkingVariables::from_array(&chaining_value)
+        );
+
+        assert_eq!(trace.digest().unwrap().to_hex(), crate::sha_256(msg));
+    }
+}
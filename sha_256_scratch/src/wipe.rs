@@ -0,0 +1,38 @@
+//!    This module overwrites memory that held secret material (keys, key pads, message blocks,
+//!    intermediate hash values) once it isn't needed anymore.
+//!
+//!    `ptr::write_volatile` can't be used since the crate forbids unsafe code, so the zeros are
+//!    written normally, then the buffer is given to `black_box` behind a compiler fence: the
+//!    optimizer has to assume the zeros are read, and can't remove them as dead stores.
+//!    Like `black_box` itself, this is a best effort rather than a guarantee, and copies the
+//!    compiler made in registers or on the stack before are out of reach.
+//!    `wipe` is public so that callers can wipe the keys they pass to `Hmac` the same way.
+
+use std::hint::black_box;
+use std::sync::atomic::{compiler_fence, Ordering};
+
+/// Overwrites every value of `values` with its default (zero)
+pub fn wipe<T: Copy + Default>(values: &mut [T]) {
+    for value in values.iter_mut() {
+        *value = T::default();
+    }
+
+    black_box(values);
+    compiler_fence(Ordering::SeqCst);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wipe_test() {
+        let mut bytes = [0xa5u8; 64];
+        wipe(&mut bytes);
+        assert_eq!(bytes, [0u8; 64]);
+
+        let mut words = vec![u32::MAX; 8];
+        wipe(&mut words);
+        assert_eq!(words, vec![0; 8]);
+    }
+}
@@ -2,8 +2,12 @@
 ///    It stores intermediary values for the compression function.
 ///    
 ///    All variables names in this module (a, b, ..., h) are the same as in the specification's formulas.
+use crate::error::Error;
+use crate::wipe::wipe;
 
-#[derive(Debug, PartialEq)]
+/// The compression function updates one value in place and wipes it once per block with `wipe`,
+/// as the variables are derived from the message and from the key in HMAC
+#[derive(Debug, Clone, PartialEq)]
 pub struct WorkingVariables {
     pub a: u32,
     pub b: u32,
@@ -16,35 +20,77 @@ pub struct WorkingVariables {
 }
 
 impl WorkingVariables {
-    pub fn new(val: &[u32]) -> WorkingVariables {
+    /// Hash state of unchecked length, which must be exactly 8 words
+    pub fn new(val: &[u32]) -> Result<WorkingVariables, Error> {
+        let val: &[u32; 8] = val
+            .try_into()
+            .map_err(|_| Error::InvalidStateLength(val.len()))?;
+
+        Ok(Self::from_array(val))
+    }
+
+    pub fn from_array(val: &[u32; 8]) -> WorkingVariables {
+        let [a, b, c, d, e, f, g, h] = *val;
+
         Self {
-            a: val[0],
-            b: val[1],
-            c: val[2],
-            d: val[3],
-            e: val[4],
-            f: val[5],
-            g: val[6],
-            h: val[7],
+            a,
+            b,
+            c,
+            d,
+            e,
+            f,
+            g,
+            h,
         }
     }
 
-    pub fn iter(&self) -> Iter {
+    pub fn iter(&self) -> Iter<'_> {
         Iter {
             inner: self,
             index: 0,
         }
     }
 
-    pub fn update(&mut self, hash: &[u32]) {
-        self.a = hash[0];
-        self.b = hash[1];
-        self.c = hash[2];
-        self.d = hash[3];
-        self.e = hash[4];
-        self.f = hash[5];
-        self.g = hash[6];
-        self.h = hash[7];
+    /// Hash state of unchecked length, which must be exactly 8 words
+    pub fn update(&mut self, hash: &[u32]) -> Result<(), Error> {
+        let hash: &[u32; 8] = hash
+            .try_into()
+            .map_err(|_| Error::InvalidStateLength(hash.len()))?;
+
+        self.update_from_array(hash);
+        Ok(())
+    }
+
+    pub(crate) fn wipe(&mut self) {
+        let Self {
+            a,
+            b,
+            c,
+            d,
+            e,
+            f,
+            g,
+            h,
+        } = self;
+
+        for var in [a, b, c, d, e, f, g, h] {
+            wipe(std::slice::from_mut(var));
+        }
+    }
+
+    pub fn update_from_array(&mut self, hash: &[u32; 8]) {
+        [
+            self.a, self.b, self.c, self.d, self.e, self.f, self.g, self.h,
+        ] = *hash;
+    }
+}
+
+/// For hash values of unchecked length, which must be exactly 8 words
+impl TryFrom<&[u32]> for WorkingVariables {
+    type Error = Error;
+
+    fn try_from(val: &[u32]) -> Result<WorkingVariables, Error> {
+        Self::new(val)
     }
 }
 
@@ -73,3 +119,59 @@ impl<'a> Iterator for Iter<'a> {
         Some(ret)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn from_slice() {
+        let words: Vec<u32> = (1..=8).collect();
+        let vars = WorkingVariables::try_from(&words[..]).unwrap();
+        assert_eq!(vars.iter().copied().collect::<Vec<_>>(), words);
+
+        assert_eq!(
+            WorkingVariables::try_from(&words[..7]),
+            Err(Error::InvalidStateLength(7))
+        );
+        assert_eq!(
+            WorkingVariables::try_from(&[0u32; 9][..]),
+            Err(Error::InvalidStateLength(9))
+        );
+    }
+
+    #[test]
+    fn new() {
+        let words: Vec<u32> = (1..=8).collect();
+        let vars = WorkingVariables::new(&words).unwrap();
+        assert_eq!(
+            vars,
+            WorkingVariables::from_array(&[1, 2, 3, 4, 5, 6, 7, 8])
+        );
+
+        assert_eq!(
+            WorkingVariables::new(&words[..3]),
+            Err(Error::InvalidStateLength(3))
+        );
+    }
+
+    #[test]
+    fn update() {
+        let mut vars = WorkingVariables::from_array(&[0; 8]);
+        vars.update(&[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+
+        assert_eq!(
+            vars,
+            WorkingVariables::from_array(&[1, 2, 3, 4, 5, 6, 7, 8])
+        );
+
+        assert_eq!(vars.update(&[0; 7]), Err(Error::InvalidStateLength(7)));
+        assert_eq!(
+            vars,
+            WorkingVariables::from_array(&[1, 2, 3, 4, 5, 6, 7, 8])
+        );
+
+        vars.update_from_array(&[0; 8]);
+        assert_eq!(vars, WorkingVariables::from_array(&[0; 8]));
+    }
+}